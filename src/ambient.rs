@@ -0,0 +1,307 @@
+//! Automatic brightness from ambient light sensors.
+//!
+//! [`LightSensor`] reads illuminance from a Linux IIO device, [`LuxCurve`] maps it to a
+//! brightness percentage, and [`AutoBrightness`] smooths the result and applies it to a
+//! [`DisplayGroup`] when it has changed by more than a threshold.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::backend::VcpBackend;
+use crate::group::DisplayGroup;
+use crate::transition::CancelToken;
+
+/// Where the kernel lists IIO devices.
+pub const IIO_DEVICES: &str = "/sys/bus/iio/devices";
+
+fn read_f64(path: &Path) -> io::Result<f64> {
+    fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// An illuminance channel of an IIO device, e.g. `in_illuminance_raw`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightSensor {
+    /// The `_input` or `_raw` attribute file.
+    pub path: PathBuf,
+    /// Scale and offset for raw values: lux is `(raw + offset) * scale`.
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl LightSensor {
+    /// Open the illuminance channel of an IIO device directory.
+    ///
+    /// A processed `_input` channel is preferred over a `_raw` one. Returns `None` if the device
+    /// has no illuminance channel.
+    pub fn open(device: &Path) -> io::Result<Option<Self>> {
+        let mut input = None;
+        let mut raw = None;
+        for entry in fs::read_dir(device)? {
+            let name = entry?.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if !name.starts_with("in_illuminance") {
+                continue;
+            }
+            if name.ends_with("_input") {
+                input.get_or_insert_with(|| name.to_owned());
+            } else if name.ends_with("_raw") {
+                raw.get_or_insert_with(|| name.to_owned());
+            }
+        }
+
+        if let Some(input) = input {
+            return Ok(Some(LightSensor {
+                path: device.join(input),
+                scale: 1.0,
+                offset: 0.0,
+            }));
+        }
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+
+        // the scale and offset can be per-channel (in_illuminance0_scale) or shared
+        let channel = raw.trim_end_matches("_raw");
+        let attr = |suffix: &str, default: f64| {
+            [
+                format!("{channel}_{suffix}"),
+                format!("in_illuminance_{suffix}"),
+            ]
+            .iter()
+            .map(|f| device.join(f))
+            .find(|p| p.exists())
+            .map_or(Ok(default), |p| read_f64(&p))
+        };
+
+        Ok(Some(LightSensor {
+            path: device.join(&raw),
+            scale: attr("scale", 1.0)?,
+            offset: attr("offset", 0.0)?,
+        }))
+    }
+
+    /// Find the light sensors among the IIO devices in `root`, usually [`IIO_DEVICES`].
+    pub fn discover(root: &Path) -> io::Result<Vec<Self>> {
+        let mut devices: Vec<_> = fs::read_dir(root)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<io::Result<_>>()?;
+        devices.sort();
+
+        let mut sensors = Vec::new();
+        for device in devices {
+            if let Some(sensor) = Self::open(&device)? {
+                sensors.push(sensor);
+            }
+        }
+
+        Ok(sensors)
+    }
+
+    /// Read the current illuminance in lux.
+    pub fn read_lux(&self) -> io::Result<f64> {
+        Ok(((read_f64(&self.path)? + self.offset) * self.scale).max(0.0))
+    }
+}
+
+/// Mapping from illuminance to brightness, as points of (lux, percent).
+///
+/// Brightness is interpolated linearly over the logarithm of the illuminance, which matches how
+/// brightness is perceived, and is constant outside the first and last points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LuxCurve {
+    points: Vec<(f64, f64)>,
+}
+
+impl Default for LuxCurve {
+    fn default() -> Self {
+        Self::new(vec![
+            (0.0, 10.0),
+            (10.0, 20.0),
+            (100.0, 45.0),
+            (1000.0, 80.0),
+            (10000.0, 100.0),
+        ])
+    }
+}
+
+impl LuxCurve {
+    /// Build a curve from (lux, percent) points, in any order.
+    ///
+    /// Panics if `points` is empty.
+    pub fn new(mut points: Vec<(f64, f64)>) -> Self {
+        assert!(!points.is_empty(), "a lux curve needs at least one point");
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        LuxCurve { points }
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// Brightness percentage for an illuminance.
+    pub fn brightness(&self, lux: f64) -> f64 {
+        let log = |lux: f64| (lux.max(0.0) + 1.0).log10();
+        let x = log(lux);
+
+        let upper = self.points.partition_point(|p| log(p.0) < x);
+        if upper == 0 {
+            return self.points[0].1;
+        }
+        let Some(&(hi_lux, hi)) = self.points.get(upper) else {
+            return self.points[upper - 1].1;
+        };
+
+        let (lo_lux, lo) = self.points[upper - 1];
+        let t = (x - log(lo_lux)) / (log(hi_lux) - log(lo_lux));
+        lo + (hi - lo) * t
+    }
+}
+
+/// Drives display brightness from a light sensor.
+#[derive(Debug, Clone)]
+pub struct AutoBrightness {
+    pub curve: LuxCurve,
+    /// Time constant of the exponential smoothing of the target brightness. Larger is slower.
+    pub smoothing: Duration,
+    /// Minimum change in percentage points before the brightness is updated.
+    pub hysteresis: f64,
+    /// Time between sensor readings in [`run`](Self::run).
+    pub interval: Duration,
+    smoothed: Option<f64>,
+    applied: Option<f64>,
+}
+
+impl Default for AutoBrightness {
+    fn default() -> Self {
+        Self::new(LuxCurve::default())
+    }
+}
+
+impl AutoBrightness {
+    pub fn new(curve: LuxCurve) -> Self {
+        AutoBrightness {
+            curve,
+            smoothing: Duration::from_secs(5),
+            hysteresis: 5.0,
+            interval: Duration::from_secs(1),
+            smoothed: None,
+            applied: None,
+        }
+    }
+
+    /// The brightness that was last applied.
+    pub fn applied(&self) -> Option<f64> {
+        self.applied
+    }
+
+    /// Feed a sensor reading taken `elapsed` after the previous one.
+    ///
+    /// Returns the brightness to apply, if it moved past the hysteresis threshold. The first
+    /// reading is always applied. Call [`mark_applied`](Self::mark_applied) once it has been.
+    pub fn step(&mut self, lux: f64, elapsed: Duration) -> Option<f64> {
+        let target = self.curve.brightness(lux);
+        let smoothed = match self.smoothed {
+            Some(prev) if !self.smoothing.is_zero() => {
+                let alpha = 1.0 - (-elapsed.as_secs_f64() / self.smoothing.as_secs_f64()).exp();
+                prev + (target - prev) * alpha
+            }
+            _ => target,
+        };
+        self.smoothed = Some(smoothed);
+
+        match self.applied {
+            Some(applied) if (smoothed - applied).abs() < self.hysteresis => None,
+            _ => Some(smoothed),
+        }
+    }
+
+    /// Record that a brightness returned by [`step`](Self::step) was applied.
+    pub fn mark_applied(&mut self, percent: f64) {
+        self.applied = Some(percent);
+    }
+
+    /// Read the sensor every `interval` and set the brightness of the group until cancelled.
+    ///
+    /// If setting the brightness fails on any display, it is tried again after the next reading.
+    /// Sensor errors end the loop.
+    pub fn run<B: VcpBackend + Send>(
+        &mut self,
+        sensor: &LightSensor,
+        group: &mut DisplayGroup<B>,
+        cancel: &CancelToken,
+    ) -> io::Result<()> {
+        let mut last = Instant::now();
+        while !cancel.is_cancelled() {
+            let lux = sensor.read_lux()?;
+            let now = Instant::now();
+            let target = self.step(lux, now - last);
+            last = now;
+
+            if let Some(percent) = target
+                && group.set_brightness(percent).iter().all(Result::is_ok)
+            {
+                self.mark_applied(percent);
+            }
+
+            std::thread::sleep(self.interval);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fake_sysfs() {
+        let root = std::env::temp_dir().join(format!("libddcutil2-iio-{0}", std::process::id()));
+        let processed = root.join("iio:device0");
+        let raw = root.join("iio:device1");
+        let accel = root.join("iio:device2");
+        for dir in [&processed, &raw, &accel] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(processed.join("in_illuminance_input"), "250.5\n").unwrap();
+        fs::write(raw.join("in_illuminance0_raw"), "1000\n").unwrap();
+        fs::write(raw.join("in_illuminance_scale"), "0.25\n").unwrap();
+        fs::write(raw.join("in_illuminance0_offset"), "-200\n").unwrap();
+        fs::write(accel.join("in_accel_x_raw"), "3\n").unwrap();
+
+        let sensors = LightSensor::discover(&root).unwrap();
+        let lux: Vec<_> = sensors.iter().map(|s| s.read_lux().unwrap()).collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(lux, [250.5, 200.0]);
+    }
+
+    #[test]
+    fn curve_and_hysteresis() {
+        let curve = LuxCurve::new(vec![(1000.0, 80.0), (0.0, 10.0), (99.0, 50.0)]);
+        assert_eq!(curve.brightness(0.0), 10.0);
+        assert_eq!(curve.brightness(99.0), 50.0);
+        assert_eq!(curve.brightness(1e6), 80.0);
+        assert!((curve.brightness(9.0) - 30.0).abs() < 1e-9);
+
+        let mut auto = AutoBrightness::new(curve);
+        auto.smoothing = Duration::ZERO;
+        assert_eq!(auto.step(99.0, Duration::ZERO), Some(50.0));
+        auto.mark_applied(50.0);
+        assert_eq!(auto.step(110.0, Duration::from_secs(1)), None);
+        assert!(auto.step(0.0, Duration::from_secs(1)).is_some());
+
+        auto.smoothing = Duration::from_secs(10);
+        auto.mark_applied(10.0);
+        let halfway = auto
+            .step(99.0, Duration::from_secs_f64(10.0 * 2f64.ln()))
+            .unwrap();
+        assert!((halfway - 30.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::display::{Display, VcpValue};
+use crate::err::Result;
+
+/// The VCP operations of a display, so code can run against something other than real hardware.
+///
+/// [`Display`] implements this by calling the library. With the `trace` feature,
+/// [`trace::Recorder`](crate::trace::Recorder) records the calls of another backend and
+/// [`trace::Replay`](crate::trace::Replay) plays them back without a monitor.
+///
+/// [`DisplayGroup`](crate::DisplayGroup) and [`transition`](crate::transition()) work with any
+/// backend.
+pub trait VcpBackend {
+    fn get_vcp_value(&self, code: u8) -> Result<VcpValue>;
+
+    fn set_vcp_value(&self, code: u8, value: u16) -> Result<()>;
+
+    fn get_capabilities_string(&self) -> Result<String>;
+}
+
+impl VcpBackend for Display {
+    fn get_vcp_value(&self, code: u8) -> Result<VcpValue> {
+        Display::get_vcp_value(self, code)
+    }
+
+    fn set_vcp_value(&self, code: u8, value: u16) -> Result<()> {
+        Display::set_vcp_value(self, code, value)
+    }
+
+    fn get_capabilities_string(&self) -> Result<String> {
+        Display::get_capabilities_string(self)
+    }
+}
+
+impl<B: VcpBackend + ?Sized> VcpBackend for &B {
+    fn get_vcp_value(&self, code: u8) -> Result<VcpValue> {
+        (**self).get_vcp_value(code)
+    }
+
+    fn set_vcp_value(&self, code: u8, value: u16) -> Result<()> {
+        (**self).set_vcp_value(code, value)
+    }
+
+    fn get_capabilities_string(&self) -> Result<String> {
+        (**self).get_capabilities_string()
+    }
+}
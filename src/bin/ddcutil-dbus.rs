@@ -0,0 +1,30 @@
+//! Publish the connected displays on D-Bus, see [`libddcutil2::dbus`].
+//!
+//! Usage: `ddcutil-dbus [--system] [--verbose]...`
+use libddcutil2::dbus::{BUS_NAME, Service};
+use libddcutil2::{OutputLevel, lib_set_output_level};
+use zbus::blocking::connection::Builder;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut system = false;
+    let mut verbosity = 0;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--system" => system = true,
+            "-v" | "--verbose" => verbosity += 1,
+            _ => return Err(format!("unknown argument {arg:?}").into()),
+        }
+    }
+
+    lib_set_output_level(OutputLevel::from_verbosity(verbosity));
+
+    let builder = if system {
+        Builder::system()?
+    } else {
+        Builder::session()?
+    };
+    let service = Service::new(builder.name(BUS_NAME)?.build()?)?;
+    service.run()?;
+
+    Ok(())
+}
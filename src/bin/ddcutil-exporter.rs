@@ -0,0 +1,41 @@
+//! Export monitor state to Prometheus, see [`libddcutil2::exporter`].
+//!
+//! Usage: `ddcutil-exporter [--listen ADDR] [--interval SECS] [--feature CODE]...`
+//!
+//! Feature codes can be given in hex (`0x10`) or decimal. By default brightness, contrast, power
+//! mode and usage hours are read every 60 seconds, and metrics are served on `127.0.0.1:9849`.
+use std::net::TcpListener;
+use std::time::Duration;
+
+use libddcutil2::exporter::{DEFAULT_FEATURES, Exporter};
+
+fn parse_code(s: &str) -> Result<u8, std::num::ParseIntError> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut listen = "127.0.0.1:9849".to_owned();
+    let mut interval = Duration::from_secs(60);
+    let mut features = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--listen" => listen = value()?,
+            "--interval" => interval = Duration::from_secs(value()?.parse()?),
+            "--feature" => features.push(parse_code(&value()?)?),
+            _ => return Err(format!("unknown argument {arg:?}").into()),
+        }
+    }
+    if features.is_empty() {
+        features = DEFAULT_FEATURES.to_vec();
+    }
+
+    let listener = TcpListener::bind(&listen)?;
+    Exporter::new(features, interval).run(&listener)?;
+    Ok(())
+}
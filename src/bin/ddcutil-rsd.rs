@@ -0,0 +1,67 @@
+//! Serve the connected displays over JSON-RPC on a Unix socket, see [`libddcutil2::rsd`].
+//!
+//! Usage: `ddcutil-rsd [--socket PATH] [--socket-mode MODE] [--socket-group GID]
+//! [--allow-uid UID]... [--allow-gid GID]... [--restrict-read]`
+//!
+//! The socket is created with mode `0660` (`--socket-mode`, in octal), so only the user running
+//! the daemon and the socket's group (`--socket-group`) can connect. Of those, anyone can read,
+//! and only the user running the daemon can change settings. `--allow-uid` and `--allow-gid`
+//! grant write access, `--restrict-read` limits reading to the same clients.
+use std::os::unix::fs::{PermissionsExt, chown};
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+
+use libddcutil2::events::{self, WatchClasses};
+use libddcutil2::rsd::{AccessPolicy, Daemon};
+
+fn default_socket() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/run"))
+        .join("ddcutil-rsd.sock")
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut socket = default_socket();
+    let mut mode = 0o660;
+    let mut group = None;
+    let mut policy = AccessPolicy::default();
+    let mut restrict_read = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--socket" => socket = value()?.into(),
+            "--socket-mode" => mode = u32::from_str_radix(&value()?, 8)?,
+            "--socket-group" => group = Some(value()?.parse()?),
+            "--allow-uid" => policy.write.uids.push(value()?.parse()?),
+            "--allow-gid" => policy.write.gids.push(value()?.parse()?),
+            "--restrict-read" => restrict_read = true,
+            _ => return Err(format!("unknown argument {arg:?}").into()),
+        }
+    }
+    if restrict_read {
+        policy.read = policy.write.clone();
+    }
+
+    // a socket left behind by a previous run would make bind fail
+    match std::fs::remove_file(&socket) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let listener = UnixListener::bind(&socket)?;
+    // access is also checked per request from the peer credentials
+    if group.is_some() {
+        chown(&socket, None, group)?;
+    }
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(mode))?;
+
+    let daemon = Daemon::new(policy);
+    let _subscription = daemon.watch()?;
+    events::start_watch_displays(WatchClasses::All)?;
+
+    daemon.serve(&listener)?;
+    Ok(())
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::capabilities::DisplayCapabilities;
+use crate::display::{Display, VcpValue};
+use crate::display_info::DisplayInfo;
+use crate::err::Result;
+use crate::events::{self, DisplayEventType, EventSubscription};
+use crate::to_hex;
+
+/// Identity of a physical monitor, which stays the same when it is connected to a different bus.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MonitorId {
+    pub manufacturer: String,
+    pub model: String,
+    pub serial_number: String,
+    pub product_code: u16,
+    /// Hex-encoded EDID, which also distinguishes monitors without a serial number string.
+    pub edid: String,
+}
+
+impl MonitorId {
+    pub fn from_info(info: &DisplayInfo) -> Self {
+        MonitorId {
+            manufacturer: info.manufacturer().to_owned(),
+            model: info.model().to_owned(),
+            serial_number: info.serial_number().to_owned(),
+            product_code: info.product_code(),
+            edid: to_hex(info.edid_bytes()),
+        }
+    }
+}
+
+/// A cached value, with the time it was read from the display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cached<T> {
+    pub value: T,
+    pub updated: SystemTime,
+}
+
+impl<T> Cached<T> {
+    fn now(value: T) -> Self {
+        Cached {
+            value,
+            updated: SystemTime::now(),
+        }
+    }
+
+    /// Whether the value was read within `ttl`.
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        self.updated
+            .elapsed()
+            .map(|age| age <= ttl)
+            .unwrap_or(false)
+    }
+}
+
+/// Last known state of a monitor.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonitorState {
+    /// Unparsed capabilities string.
+    pub capabilities: Option<Cached<String>>,
+    pub values: HashMap<u8, Cached<VcpValue>>,
+}
+
+/// On-disk format of the cache. Keys of JSON maps must be strings, so monitors are stored as a
+/// list instead.
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    monitors: Vec<(MonitorId, MonitorState)>,
+}
+
+/// Cache of monitor capabilities and VCP values, to avoid slow DDC reads.
+///
+/// Values are served from the cache if they were read within the TTL. Writes through
+/// [`CachedDisplay`] invalidate the cached value, and [`StateCache::invalidate_on_hotplug`] clears
+/// the cache when displays are connected or disconnected. The cache can be persisted to disk,
+/// e.g. so a UI can show the last known values immediately on startup.
+#[derive(Debug)]
+pub struct StateCache {
+    ttl: Duration,
+    monitors: Mutex<HashMap<MonitorId, MonitorState>>,
+}
+
+impl StateCache {
+    pub fn new(ttl: Duration) -> Self {
+        StateCache {
+            ttl,
+            monitors: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Default location of the cache file: `$XDG_CACHE_HOME/libddcutil2/state.json`, falling back
+    /// to `~/.cache` if `XDG_CACHE_HOME` is not set.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+
+        Some(base.join("libddcutil2").join("state.json"))
+    }
+
+    /// Load a cache from a file. A missing file gives an empty cache.
+    pub fn load(path: &Path, ttl: Duration) -> io::Result<Self> {
+        let cache = Self::new(ttl);
+
+        let contents = match fs::read(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(e),
+        };
+
+        let file: CacheFile = serde_json::from_slice(&contents)?;
+        *cache.lock() = file.monitors.into_iter().collect();
+
+        Ok(cache)
+    }
+
+    /// Save the cache to a file, creating the parent directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = CacheFile {
+            monitors: self
+                .lock()
+                .iter()
+                .map(|(id, state)| (id.clone(), state.clone()))
+                .collect(),
+        };
+
+        // write to a temporary file first so a crash can't leave a truncated cache
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(&file)?)?;
+        fs::rename(tmp, path)
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<MonitorId, MonitorState>> {
+        self.monitors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get a snapshot of the cached state of a monitor, regardless of age.
+    pub fn state(&self, id: &MonitorId) -> Option<MonitorState> {
+        self.lock().get(id).cloned()
+    }
+
+    /// Get the cached value of a feature, regardless of age.
+    pub fn cached_vcp_value(&self, id: &MonitorId, code: u8) -> Option<Cached<VcpValue>> {
+        self.lock().get(id)?.values.get(&code).cloned()
+    }
+
+    pub fn insert_vcp_value(&self, id: &MonitorId, code: u8, value: VcpValue) {
+        self.lock()
+            .entry(id.clone())
+            .or_default()
+            .values
+            .insert(code, Cached::now(value));
+    }
+
+    pub fn insert_capabilities(&self, id: &MonitorId, caps: String) {
+        self.lock().entry(id.clone()).or_default().capabilities = Some(Cached::now(caps));
+    }
+
+    /// Forget the cached value of one feature.
+    pub fn invalidate_vcp_value(&self, id: &MonitorId, code: u8) {
+        if let Some(state) = self.lock().get_mut(id) {
+            state.values.remove(&code);
+        }
+    }
+
+    /// Forget everything cached for one monitor.
+    pub fn invalidate(&self, id: &MonitorId) {
+        self.lock().remove(id);
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Clear the cache whenever a display is connected or disconnected.
+    ///
+    /// Events are only reported while displays are watched, see
+    /// [`crate::events::start_watch_displays`].
+    pub fn invalidate_on_hotplug(self: &Arc<Self>) -> Result<EventSubscription> {
+        let cache = Arc::downgrade(self);
+        events::subscribe(move |e| {
+            if matches!(
+                e.event_type,
+                DisplayEventType::Connected | DisplayEventType::Disconnected
+            ) && let Some(cache) = cache.upgrade()
+            {
+                cache.clear();
+            }
+        })
+    }
+}
+
+/// A display whose reads go through a [`StateCache`].
+#[derive(Debug)]
+pub struct CachedDisplay<'c> {
+    cache: &'c StateCache,
+    display: Display,
+    id: MonitorId,
+}
+
+impl<'c> CachedDisplay<'c> {
+    pub fn new(cache: &'c StateCache, display: Display, id: MonitorId) -> Self {
+        CachedDisplay { cache, display, id }
+    }
+
+    /// Open the display and identify it from its info.
+    pub fn open(cache: &'c StateCache, info: &DisplayInfo) -> Result<Self> {
+        Ok(Self::new(
+            cache,
+            Display::from_display_info(info)?,
+            MonitorId::from_info(info),
+        ))
+    }
+
+    pub fn id(&self) -> &MonitorId {
+        &self.id
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// Get a VCP value, from the cache if it is within the TTL, otherwise from the display.
+    pub fn get_vcp_value(&self, code: u8) -> Result<VcpValue> {
+        match self.cache.cached_vcp_value(&self.id, code) {
+            Some(c) if c.is_fresh(self.cache.ttl) => Ok(c.value),
+            _ => self.refresh_vcp_value(code),
+        }
+    }
+
+    /// Get the last known VCP value without any DDC communication, regardless of age.
+    pub fn peek_vcp_value(&self, code: u8) -> Option<Cached<VcpValue>> {
+        self.cache.cached_vcp_value(&self.id, code)
+    }
+
+    /// Read a VCP value from the display and update the cache.
+    pub fn refresh_vcp_value(&self, code: u8) -> Result<VcpValue> {
+        let value = self.display.get_vcp_value(code)?;
+        self.cache.insert_vcp_value(&self.id, code, value.clone());
+        Ok(value)
+    }
+
+    /// Set a VCP value. The cached value is invalidated, since the display may not apply the
+    /// value exactly as written.
+    pub fn set_vcp_value(&self, code: u8, value: u16) -> Result<()> {
+        self.cache.invalidate_vcp_value(&self.id, code);
+        self.display.set_vcp_value(code, value)
+    }
+
+    /// Get the capabilities, from the cache if they are within the TTL.
+    pub fn get_capabilities(&self) -> Result<DisplayCapabilities> {
+        let cached = self
+            .cache
+            .state(&self.id)
+            .and_then(|s| s.capabilities)
+            .filter(|c| c.is_fresh(self.cache.ttl));
+
+        let caps = match cached {
+            Some(c) => c.value,
+            None => {
+                let caps = self.display.get_capabilities_string()?;
+                self.cache.insert_capabilities(&self.id, caps.clone());
+                caps
+            }
+        };
+
+        DisplayCapabilities::parse(&caps)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_id() -> MonitorId {
+        MonitorId {
+            manufacturer: "DEL".to_owned(),
+            model: "U2720Q".to_owned(),
+            serial_number: "ABC123".to_owned(),
+            product_code: 0x4321,
+            edid: "00ffffffffffff00".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_persist_roundtrip() {
+        let path = std::env::temp_dir()
+            .join(format!("libddcutil2-cache-test-{}", std::process::id()))
+            .join("state.json");
+
+        let cache = StateCache::new(Duration::from_secs(60));
+        let id = test_id();
+        let brightness = VcpValue::Continuous {
+            max: 100,
+            current: 42,
+        };
+        cache.insert_vcp_value(&id, 0x10, brightness.clone());
+        cache.insert_capabilities(&id, "(vcp(10))".to_owned());
+        cache.save(&path).unwrap();
+
+        let loaded = StateCache::load(&path, Duration::from_secs(60)).unwrap();
+        assert_eq!(loaded.state(&id), cache.state(&id));
+        assert_eq!(
+            loaded.cached_vcp_value(&id, 0x10).unwrap().value,
+            brightness
+        );
+
+        loaded.invalidate_vcp_value(&id, 0x10);
+        assert!(loaded.cached_vcp_value(&id, 0x10).is_none());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_freshness() {
+        let fresh = Cached::now(1);
+        assert!(fresh.is_fresh(Duration::from_secs(1)));
+
+        let stale = Cached {
+            value: 1,
+            updated: SystemTime::now() - Duration::from_secs(10),
+        };
+        assert!(!stale.is_fresh(Duration::from_secs(1)));
+    }
+}
@@ -1,9 +1,9 @@
 use std::borrow::Cow;
-use std::ffi::CStr;
-use std::slice;
+use std::ffi::{CStr, CString};
+use std::{ptr, slice};
 
 use crate::feature_metadata::FeatureSet;
-use crate::{MccsVersion, sys};
+use crate::{DdcError, MccsVersion, Result, sys};
 
 // TODO better name for htis?
 
@@ -36,6 +36,19 @@ impl Drop for DisplayCapabilities {
 }
 
 impl<'a> DisplayCapabilities {
+    /// Parse a capabilities string, as returned by [`crate::Display::get_capabilities_string`].
+    pub fn parse(caps: &str) -> Result<Self> {
+        let caps = CString::new(caps).map_err(|_| DdcError::from_kind_rc(sys::DDCRC_ARG))?;
+        let mut parsed = DisplayCapabilities(ptr::null_mut());
+
+        unsafe {
+            let rc = sys::ddca_parse_capabilities_string(caps.as_ptr() as *mut _, &mut parsed.0);
+            DdcError::check(rc)?;
+        }
+
+        Ok(parsed)
+    }
+
     pub fn version(&self) -> MccsVersion {
         unsafe { *self.0 }.version_spec
     }
@@ -57,6 +70,11 @@ impl<'a> DisplayCapabilities {
         }
     }
 
+    /// Find the capabilities entry for a feature code, if the display declares it.
+    pub fn vcp_code(&'a self, code: u8) -> Option<&'a CapVcp> {
+        self.vcp_codes().iter().find(|c| c.feature_code() == code)
+    }
+
     pub fn get_messages(&'a self) -> Vec<Cow<'a, str>> {
         if unsafe { (*self.0).messages.is_null() || (*self.0).msg_ct == 0 } {
             return vec![];
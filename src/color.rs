@@ -0,0 +1,438 @@
+use crate::{DdcError, DdcErrorKind, Display, FeatureSet, Result, VcpValue, sys};
+
+/// VCP feature code for the color temperature increment, in Kelvin.
+const COLOR_TEMPERATURE_INCREMENT: u8 = 0x0B;
+/// VCP feature code for the user color temperature, in increments above [`BASE_KELVIN`].
+const COLOR_TEMPERATURE: u8 = 0x0C;
+/// VCP feature code for the color preset.
+const COLOR_PRESET: u8 = 0x14;
+
+/// Color temperature of a 0x0C value of 0.
+const BASE_KELVIN: u32 = 3000;
+
+/// Values of the color preset feature that select a color temperature. sRGB comes after the
+/// explicit 6500K preset, so the latter is preferred when setting a temperature.
+const PRESETS: [(u8, u32); 9] = [
+    (0x03, 4000),
+    (0x04, 5000),
+    (0x05, 6500),
+    (0x01, 6500),
+    (0x06, 7500),
+    (0x07, 8200),
+    (0x08, 9300),
+    (0x09, 10000),
+    (0x0A, 11500),
+];
+
+/// Color temperature selected by a value of the color preset feature (0x14), if any.
+fn preset_kelvin(code: u8) -> Option<u32> {
+    PRESETS.iter().find(|p| p.0 == code).map(|p| p.1)
+}
+
+/// The preset among `supported` closest to `kelvin`. All presets are considered if `supported`
+/// is empty.
+fn nearest_preset(kelvin: u32, supported: &[u8]) -> Option<(u8, u32)> {
+    PRESETS
+        .iter()
+        .copied()
+        .filter(|p| supported.is_empty() || supported.contains(&p.0))
+        .min_by_key(|p| p.1.abs_diff(kelvin))
+}
+
+fn is_unsupported(e: &DdcError) -> bool {
+    matches!(
+        e.kind(),
+        DdcErrorKind::ReportedUnsupported | DdcErrorKind::DeterminedUnsupported
+    )
+}
+
+fn non_table_value(value: VcpValue) -> (u16, u16) {
+    match value {
+        VcpValue::Continuous { max, current } => (max, current),
+        VcpValue::NonContinuous { sh, sl } => (0, u16::from_be_bytes([sh, sl])),
+        VcpValue::Table(_) => (0, 0),
+    }
+}
+
+impl Display {
+    /// Read the user color temperature as (increment in Kelvin, current value, max value).
+    ///
+    /// A display that reports an increment of 0 is treated as not supporting the feature.
+    fn user_color_temperature(&self) -> Result<(u32, u16, u16)> {
+        let (_, increment) = non_table_value(self.get_vcp_value(COLOR_TEMPERATURE_INCREMENT)?);
+        if increment == 0 {
+            return Err(DdcError::from_kind_rc(sys::DDCRC_DETERMINED_UNSUPPORTED));
+        }
+        let (max, current) = non_table_value(self.get_vcp_value(COLOR_TEMPERATURE)?);
+        Ok((increment.into(), current, max))
+    }
+
+    /// Color presets (0x14) declared in the capabilities, or none if there's no list.
+    fn supported_color_presets(&self) -> Result<Vec<u8>> {
+        let caps = self.get_capabilities()?;
+        Ok(caps
+            .vcp_code(COLOR_PRESET)
+            .map(|cap| cap.values().to_vec())
+            .unwrap_or_default())
+    }
+
+    /// Get the color temperature in Kelvin.
+    ///
+    /// Uses the user color temperature (0x0C) if the display supports it, otherwise the color
+    /// preset (0x14). Fails with `DDCRC_INTERPRETATION_FAILED` if the current preset isn't a
+    /// color temperature, e.g. a user preset.
+    pub fn color_temperature(&self) -> Result<u32> {
+        match self.user_color_temperature() {
+            Ok((increment, current, _)) => Ok(BASE_KELVIN + current as u32 * increment),
+            Err(e) if is_unsupported(&e) => {
+                let (_, code) = non_table_value(self.get_vcp_value(COLOR_PRESET)?);
+                preset_kelvin(code as u8)
+                    .ok_or_else(|| DdcError::from_kind_rc(sys::DDCRC_INTERPRETATION_FAILED))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Set the color temperature in Kelvin.
+    ///
+    /// The temperature is rounded to the display's increment and clamped to its range. If the
+    /// display doesn't support the user color temperature (0x0C), the closest color preset
+    /// (0x14) that it supports is selected instead.
+    ///
+    /// Returns the temperature that was set.
+    pub fn set_color_temperature(&self, kelvin: u32) -> Result<u32> {
+        match self.user_color_temperature() {
+            Ok((increment, _, max)) => {
+                let value = kelvin_to_value(kelvin, increment, max);
+                self.set_vcp_value(COLOR_TEMPERATURE, value)?;
+                Ok(BASE_KELVIN + value as u32 * increment)
+            }
+            Err(e) if is_unsupported(&e) => {
+                let supported = self.supported_color_presets()?;
+                let (code, preset) = nearest_preset(kelvin, &supported)
+                    .ok_or_else(|| DdcError::from_kind_rc(sys::DDCRC_DETERMINED_UNSUPPORTED))?;
+                self.set_vcp_value(COLOR_PRESET, code.into())?;
+                Ok(preset)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// VCP feature codes for the six-axis hue, in the order of the fields of [`SixAxis`].
+const HUE: [u8; 6] = [0x9B, 0x9C, 0x9D, 0x9E, 0x9F, 0xA0];
+/// VCP feature codes for the six-axis saturation.
+const SATURATION: [u8; 6] = [0x59, 0x5A, 0x5B, 0x5C, 0x5D, 0x5E];
+/// VCP feature codes for the video gain (drive), in the order of the fields of [`Rgb`].
+const GAIN: [u8; 3] = [0x16, 0x18, 0x1A];
+/// VCP feature codes for the video black level.
+const BLACK_LEVEL: [u8; 3] = [0x6C, 0x6E, 0x70];
+/// VCP feature code for the gamma.
+const GAMMA: u8 = 0x72;
+
+/// A feature value and its maximum, as reported by the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Level {
+    pub value: u16,
+    /// `None` if the display declares the feature as non-continuous. `value` is then the raw
+    /// SH/SL value, which has no range.
+    pub max: Option<u16>,
+}
+
+/// Red, green and blue values. `None` if the display doesn't support the feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rgb {
+    pub red: Option<Level>,
+    pub green: Option<Level>,
+    pub blue: Option<Level>,
+}
+
+impl Rgb {
+    fn slots(&self) -> [&Option<Level>; 3] {
+        [&self.red, &self.green, &self.blue]
+    }
+
+    fn slots_mut(&mut self) -> [&mut Option<Level>; 3] {
+        [&mut self.red, &mut self.green, &mut self.blue]
+    }
+}
+
+/// Values for the six color axes. `None` if the display doesn't support the feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SixAxis {
+    pub red: Option<Level>,
+    pub yellow: Option<Level>,
+    pub green: Option<Level>,
+    pub cyan: Option<Level>,
+    pub blue: Option<Level>,
+    pub magenta: Option<Level>,
+}
+
+impl SixAxis {
+    fn slots(&self) -> [&Option<Level>; 6] {
+        [
+            &self.red,
+            &self.yellow,
+            &self.green,
+            &self.cyan,
+            &self.blue,
+            &self.magenta,
+        ]
+    }
+
+    fn slots_mut(&mut self) -> [&mut Option<Level>; 6] {
+        [
+            &mut self.red,
+            &mut self.yellow,
+            &mut self.green,
+            &mut self.cyan,
+            &mut self.blue,
+            &mut self.magenta,
+        ]
+    }
+}
+
+/// Gamma (VCP feature 0x72).
+///
+/// MCCS encodes the gamma in the SL byte as `(SL + 100) / 100`, covering 1.00 to 3.55.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gamma {
+    pub value: f64,
+    /// The SH byte. MCCS uses it for the tolerance of the reported value; it is written back
+    /// unchanged.
+    pub sh: u8,
+}
+
+impl Gamma {
+    pub const MIN: f64 = 1.0;
+    pub const MAX: f64 = 3.55;
+
+    pub fn from_bytes(sh: u8, sl: u8) -> Self {
+        Gamma {
+            value: (sl as f64 + 100.0) / 100.0,
+            sh,
+        }
+    }
+
+    /// The SH and SL bytes, with the value rounded to the nearest 0.01 and clamped to
+    /// [`MIN`](Self::MIN)..=[`MAX`](Self::MAX).
+    pub fn to_bytes(&self) -> [u8; 2] {
+        let sl = (self.value.clamp(Self::MIN, Self::MAX) * 100.0 - 100.0).round() as u8;
+        [self.sh, sl]
+    }
+}
+
+/// The color adjustments of a display: six-axis hue and saturation, RGB gain and black level,
+/// and gamma.
+///
+/// [`read`](Self::read) them, change the fields, and [`write`](Self::write) them back. Only the
+/// features that changed are written, and if one of the writes fails the others are restored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorAdjustments {
+    pub hue: SixAxis,
+    pub saturation: SixAxis,
+    pub gain: Rgb,
+    pub black_level: Rgb,
+    pub gamma: Option<Gamma>,
+    /// Raw values as last read from or written to the display.
+    applied: Vec<(u8, u16)>,
+}
+
+impl ColorAdjustments {
+    /// Read the adjustments that the display declares in its capabilities.
+    pub fn read(display: &Display) -> Result<Self> {
+        let features = display.get_capabilities()?.get_feature_bitfield();
+        let mut adj = ColorAdjustments::default();
+
+        for (code, slot) in adj.levels_mut() {
+            *slot = match read_feature(display, &features, code)? {
+                Some(VcpValue::Continuous { max, current }) => Some(Level {
+                    value: current,
+                    max: Some(max),
+                }),
+                Some(VcpValue::NonContinuous { sh, sl }) => Some(Level {
+                    value: u16::from_be_bytes([sh, sl]),
+                    max: None,
+                }),
+                _ => None,
+            };
+        }
+        adj.gamma = match read_feature(display, &features, GAMMA)? {
+            Some(VcpValue::NonContinuous { sh, sl }) => Some(Gamma::from_bytes(sh, sl)),
+            Some(VcpValue::Continuous { current, .. }) => {
+                let [sh, sl] = current.to_be_bytes();
+                Some(Gamma::from_bytes(sh, sl))
+            }
+            _ => None,
+        };
+
+        adj.applied = adj.raw_values();
+        Ok(adj)
+    }
+
+    /// Write the adjustments that changed since they were read or last written, including
+    /// features that were `None` when read and have been set since.
+    ///
+    /// Values are clamped to their maximum, if the display reported one. If a write fails, the features written
+    /// before it are set back to their previous values, on a best-effort basis, and the error
+    /// is returned. Features that had no previous value are left as written.
+    pub fn write(&mut self, display: &Display) -> Result<()> {
+        let target = self.raw_values();
+        let mut written = Vec::new();
+
+        for (code, old, new) in self.changes(&target) {
+            if let Err(e) = display.set_vcp_value(code, new) {
+                for &(code, old) in written.iter().rev() {
+                    if let Some(old) = old {
+                        let _ = display.set_vcp_value(code, old);
+                    }
+                }
+                return Err(e);
+            }
+            written.push((code, old));
+        }
+
+        self.applied = target;
+        Ok(())
+    }
+
+    fn levels(&self) -> Vec<(u8, &Option<Level>)> {
+        let hue = HUE.into_iter().zip(self.hue.slots());
+        let saturation = SATURATION.into_iter().zip(self.saturation.slots());
+        let gain = GAIN.into_iter().zip(self.gain.slots());
+        let black_level = BLACK_LEVEL.into_iter().zip(self.black_level.slots());
+        hue.chain(saturation)
+            .chain(gain)
+            .chain(black_level)
+            .collect()
+    }
+
+    fn levels_mut(&mut self) -> Vec<(u8, &mut Option<Level>)> {
+        let hue = HUE.into_iter().zip(self.hue.slots_mut());
+        let saturation = SATURATION.into_iter().zip(self.saturation.slots_mut());
+        let gain = GAIN.into_iter().zip(self.gain.slots_mut());
+        let black_level = BLACK_LEVEL.into_iter().zip(self.black_level.slots_mut());
+        hue.chain(saturation)
+            .chain(gain)
+            .chain(black_level)
+            .collect()
+    }
+
+    /// The raw value of every supported feature.
+    fn raw_values(&self) -> Vec<(u8, u16)> {
+        let levels = self.levels().into_iter().filter_map(|(code, level)| {
+            level.map(|l| (code, l.max.map_or(l.value, |max| l.value.min(max))))
+        });
+        let gamma = self
+            .gamma
+            .map(|g| (GAMMA, u16::from_be_bytes(g.to_bytes())));
+        levels.chain(gamma).collect()
+    }
+
+    /// The features whose value in `target` differs from the applied one, as (code, old, new).
+    /// `old` is `None` for features that weren't applied before.
+    fn changes(&self, target: &[(u8, u16)]) -> Vec<(u8, Option<u16>, u16)> {
+        target
+            .iter()
+            .filter_map(|&(code, new)| {
+                let old = self.applied.iter().find(|a| a.0 == code).map(|a| a.1);
+                (old != Some(new)).then_some((code, old, new))
+            })
+            .collect()
+    }
+}
+
+/// Read a feature if the display declares it, treating unsupported features as missing.
+fn read_feature(display: &Display, features: &FeatureSet, code: u8) -> Result<Option<VcpValue>> {
+    if !features.contains(code) {
+        return Ok(None);
+    }
+    match display.get_vcp_value(code) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if is_unsupported(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Value of the user color temperature feature closest to `kelvin`.
+fn kelvin_to_value(kelvin: u32, increment: u32, max: u16) -> u16 {
+    let steps = (kelvin.saturating_sub(BASE_KELVIN) + increment / 2) / increment;
+    steps.min(max.into()) as u16
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kelvin_conversion() {
+        // 3000K to 10000K in steps of 100K
+        assert_eq!(kelvin_to_value(6500, 100, 70), 35);
+        assert_eq!(kelvin_to_value(6549, 100, 70), 35);
+        assert_eq!(kelvin_to_value(6550, 100, 70), 36);
+        assert_eq!(kelvin_to_value(2000, 100, 70), 0);
+        assert_eq!(kelvin_to_value(20000, 100, 70), 70);
+
+        assert_eq!(nearest_preset(6000, &[]), Some((0x05, 6500)));
+        assert_eq!(nearest_preset(6500, &[0x01, 0x08]), Some((0x01, 6500)));
+        assert_eq!(
+            nearest_preset(3000, &[0x04, 0x05, 0x0B]),
+            Some((0x04, 5000))
+        );
+        assert_eq!(nearest_preset(3000, &[0x0B]), None);
+        assert_eq!(preset_kelvin(0x08), Some(9300));
+        assert_eq!(preset_kelvin(0x0B), None);
+    }
+
+    #[test]
+    fn adjustment_changes() {
+        assert_eq!(Gamma::from_bytes(0, 120).value, 2.2);
+        assert_eq!(Gamma::from_bytes(0x05, 0).to_bytes(), [0x05, 0]);
+        let gamma = Gamma { value: 2.4, sh: 0 };
+        assert_eq!(gamma.to_bytes(), [0, 140]);
+        assert_eq!(
+            Gamma {
+                value: 5.0,
+                ..gamma
+            }
+            .to_bytes(),
+            [0, 255]
+        );
+
+        let level = |value| {
+            Some(Level {
+                value,
+                max: Some(100),
+            })
+        };
+        let mut adj = ColorAdjustments {
+            gain: Rgb {
+                red: level(50),
+                green: level(50),
+                blue: None,
+            },
+            gamma: Some(gamma),
+            ..Default::default()
+        };
+        adj.applied = adj.raw_values();
+        assert_eq!(adj.applied, [(0x16, 50), (0x18, 50), (0x72, 140)]);
+
+        adj.gain.green = level(150);
+        adj.hue.cyan = level(10);
+        // non-continuous values have no maximum to clamp to
+        adj.black_level.red = Some(Level {
+            value: 0x0180,
+            max: None,
+        });
+        adj.gamma = Some(Gamma::from_bytes(0, 120));
+        assert_eq!(
+            adj.changes(&adj.raw_values()),
+            [
+                (0x9E, None, 10),
+                (0x18, Some(50), 100),
+                (0x6C, None, 0x0180),
+                (0x72, Some(140), 120)
+            ]
+        );
+    }
+}
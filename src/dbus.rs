@@ -0,0 +1,445 @@
+//! D-Bus service publishing displays and their VCP controls.
+//!
+//! The manager object at [`MANAGER_PATH`] lists one object per display, named after the display's
+//! I/O path, e.g. `/io/github/libddcutil2/Display/i2c_4`, and emits `DisplayAdded` and
+//! `DisplayRemoved` signals on hotplug. The `ddcutil-dbus` binary runs a [`Service`] on the session
+//! or system bus.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, mpsc};
+
+use zbus::blocking::Connection;
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+
+use crate::backend::VcpBackend;
+use crate::display::{Display, VcpValue};
+use crate::display_info::{DisplayInfo, DisplayPath, get_display_info_list};
+use crate::err::DdcError;
+use crate::events::{self, DisplayEventType, WatchClasses};
+
+pub const BUS_NAME: &str = "io.github.libddcutil2";
+pub const MANAGER_PATH: &str = "/io/github/libddcutil2";
+pub const MANAGER_INTERFACE: &str = "io.github.libddcutil2.Manager1";
+
+fn to_fdo(e: DdcError) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+/// Object path for the display at `path`.
+pub fn object_path(path: DisplayPath) -> OwnedObjectPath {
+    let name = match path {
+        DisplayPath::I2C { bus } => format!("i2c_{bus}"),
+        DisplayPath::USB { hiddev_devno } => format!("hiddev_{hiddev_devno}"),
+    };
+    ObjectPath::try_from(format!("{MANAGER_PATH}/Display/{name}"))
+        .expect("display object paths are valid")
+        .into()
+}
+
+/// What a [`DisplayObject`] needs from a display. Implemented by [`Display`]; tests use a
+/// mock.
+trait Backend: VcpBackend + Send {
+    fn get_vcp_table_value(&self, code: u8) -> crate::Result<Vec<u8>>;
+
+    fn get_formatted_vcp_value(&self, code: u8) -> crate::Result<String>;
+}
+
+impl Backend for Display {
+    fn get_vcp_table_value(&self, code: u8) -> crate::Result<Vec<u8>> {
+        Ok(Display::get_vcp_table_value(self, code)?
+            .as_slice()
+            .to_vec())
+    }
+
+    fn get_formatted_vcp_value(&self, code: u8) -> crate::Result<String> {
+        Display::get_formatted_vcp_value(self, code)
+    }
+}
+
+/// A display published on the bus.
+///
+/// Calls are serialized per display, and run on a blocking thread pool rather than the bus
+/// executor. Properties are read from the display info when the display was detected, apart from
+/// `Capabilities`, which is empty until `GetCapabilities` has read it from the monitor.
+pub struct DisplayObject {
+    backend: Arc<Mutex<Box<dyn Backend>>>,
+    capabilities: Arc<OnceLock<String>>,
+    display_no: i32,
+    manufacturer: String,
+    model: String,
+    serial_number: String,
+    product_code: u16,
+    io_path: String,
+    mccs_version: String,
+    edid: Vec<u8>,
+}
+
+impl DisplayObject {
+    pub fn open(info: &DisplayInfo) -> crate::Result<Self> {
+        let io_path = match info.path() {
+            DisplayPath::I2C { bus } => format!("/dev/i2c-{bus}"),
+            DisplayPath::USB { hiddev_devno } => format!("/dev/usb/hiddev{hiddev_devno}"),
+        };
+
+        Ok(DisplayObject {
+            backend: Arc::new(Mutex::new(Box::new(Display::from_display_info(info)?))),
+            capabilities: Arc::default(),
+            display_no: info.display_no(),
+            manufacturer: info.manufacturer().to_owned(),
+            model: info.model().to_owned(),
+            serial_number: info.serial_number().to_owned(),
+            product_code: info.product_code(),
+            io_path,
+            mccs_version: info.vcp_version().to_string(),
+            edid: info.edid_bytes().to_vec(),
+        })
+    }
+
+    /// Run `f` with the display on the blocking thread pool.
+    async fn call<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&dyn Backend) -> crate::Result<R> + Send + 'static,
+    ) -> fdo::Result<R> {
+        let backend = self.backend.clone();
+        blocking::unblock(move || {
+            let backend = backend.lock().unwrap_or_else(|e| e.into_inner());
+            f(backend.as_ref())
+        })
+        .await
+        .map_err(to_fdo)
+    }
+}
+
+#[zbus::interface(name = "io.github.libddcutil2.Display1")]
+impl DisplayObject {
+    /// Read a non-table feature. Returns (current, max) for continuous features, and (SH << 8 | SL,
+    /// 0) for non-continuous ones.
+    async fn get_vcp(&self, code: u8) -> fdo::Result<(u16, u16)> {
+        match self.call(move |d| d.get_vcp_value(code)).await? {
+            VcpValue::Continuous { max, current } => Ok((current, max)),
+            VcpValue::NonContinuous { sh, sl } => Ok((u16::from_be_bytes([sh, sl]), 0)),
+            VcpValue::Table(_) => Err(fdo::Error::InvalidArgs(format!(
+                "feature 0x{code:02x} is a table feature"
+            ))),
+        }
+    }
+
+    async fn get_table_vcp(&self, code: u8) -> fdo::Result<Vec<u8>> {
+        self.call(move |d| d.get_vcp_table_value(code)).await
+    }
+
+    /// The value as formatted by libddcutil, e.g. the name of the current input source.
+    async fn get_formatted_vcp(&self, code: u8) -> fdo::Result<String> {
+        self.call(move |d| d.get_formatted_vcp_value(code)).await
+    }
+
+    async fn set_vcp(&self, code: u8, value: u16) -> fdo::Result<()> {
+        self.call(move |d| d.set_vcp_value(code, value)).await
+    }
+
+    async fn get_capabilities(&self) -> fdo::Result<String> {
+        if let Some(caps) = self.capabilities.get() {
+            return Ok(caps.clone());
+        }
+
+        let caps = self.call(|d| d.get_capabilities_string()).await?;
+        Ok(self.capabilities.get_or_init(|| caps).clone())
+    }
+
+    /// The capabilities string, once read by `GetCapabilities`. Reading it from the monitor can
+    /// take seconds, so the property never does.
+    #[zbus(property)]
+    fn capabilities(&self) -> String {
+        self.capabilities.get().cloned().unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn display_no(&self) -> i32 {
+        self.display_no
+    }
+
+    #[zbus(property)]
+    fn manufacturer(&self) -> &str {
+        &self.manufacturer
+    }
+
+    #[zbus(property)]
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    #[zbus(property)]
+    fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    #[zbus(property)]
+    fn product_code(&self) -> u16 {
+        self.product_code
+    }
+
+    #[zbus(property)]
+    fn io_path(&self) -> &str {
+        &self.io_path
+    }
+
+    #[zbus(property)]
+    fn mccs_version(&self) -> &str {
+        &self.mccs_version
+    }
+
+    #[zbus(property)]
+    fn edid(&self) -> Vec<u8> {
+        self.edid.clone()
+    }
+}
+
+type DisplayPaths = Arc<Mutex<HashMap<DisplayPath, OwnedObjectPath>>>;
+
+/// The manager object, which lists the published displays.
+pub struct Manager {
+    displays: DisplayPaths,
+}
+
+#[zbus::interface(name = "io.github.libddcutil2.Manager1")]
+impl Manager {
+    fn list_displays(&self) -> Vec<OwnedObjectPath> {
+        let displays = self.displays.lock().unwrap_or_else(|e| e.into_inner());
+        let mut paths: Vec<_> = displays.values().cloned().collect();
+        paths.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        paths
+    }
+
+    #[zbus(signal)]
+    async fn display_added(emitter: &SignalEmitter<'_>, path: ObjectPath<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn display_removed(emitter: &SignalEmitter<'_>, path: ObjectPath<'_>)
+    -> zbus::Result<()>;
+}
+
+/// Publishes the detected displays on a connection and keeps them up to date.
+pub struct Service {
+    conn: Connection,
+    displays: DisplayPaths,
+}
+
+impl Service {
+    /// Serve the manager object on `conn`. Displays are published by [`rescan`](Self::rescan).
+    pub fn new(conn: Connection) -> zbus::Result<Self> {
+        let displays = DisplayPaths::default();
+        conn.object_server().at(
+            MANAGER_PATH,
+            Manager {
+                displays: displays.clone(),
+            },
+        )?;
+
+        Ok(Service { conn, displays })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    fn emit(&self, signal: &str, path: &OwnedObjectPath) -> zbus::Result<()> {
+        self.conn.emit_signal(
+            None::<()>,
+            MANAGER_PATH,
+            MANAGER_INTERFACE,
+            signal,
+            &(path,),
+        )
+    }
+
+    /// Publish displays that appeared and remove the ones that are gone since the last scan.
+    ///
+    /// Displays that can't be opened are skipped, and tried again on the next scan.
+    pub fn rescan(&self) -> zbus::Result<()> {
+        let infos =
+            get_display_info_list(false).map_err(|e| zbus::Error::Failure(e.to_string()))?;
+        let server = self.conn.object_server();
+        let mut displays = self.displays.lock().unwrap_or_else(|e| e.into_inner());
+
+        let gone: Vec<_> = displays
+            .keys()
+            .filter(|p| infos.as_slice().iter().all(|i| i.path() != **p))
+            .copied()
+            .collect();
+        for p in gone {
+            if let Some(path) = displays.remove(&p) {
+                server.remove::<DisplayObject, _>(&path)?;
+                self.emit("DisplayRemoved", &path)?;
+            }
+        }
+
+        for info in &infos {
+            if displays.contains_key(&info.path()) {
+                continue;
+            }
+            let Ok(object) = DisplayObject::open(info) else {
+                continue;
+            };
+
+            let path = object_path(info.path());
+            server.at(&path, object)?;
+            self.emit("DisplayAdded", &path)?;
+            displays.insert(info.path(), path);
+        }
+
+        Ok(())
+    }
+
+    /// Publish the current displays, then rescan whenever a display is connected or disconnected.
+    ///
+    /// This only returns on error.
+    pub fn run(&self) -> zbus::Result<()> {
+        let (tx, rx) = mpsc::channel();
+        let _subscription = events::subscribe(move |e| {
+            if matches!(
+                e.event_type,
+                DisplayEventType::Connected | DisplayEventType::Disconnected
+            ) {
+                let _ = tx.send(());
+            }
+        })
+        .map_err(|e| zbus::Error::Failure(e.to_string()))?;
+        events::start_watch_displays(WatchClasses::Connection)
+            .map_err(|e| zbus::Error::Failure(e.to_string()))?;
+
+        self.rescan()?;
+        while rx.recv().is_ok() {
+            self.rescan()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sys;
+    use std::os::unix::net::UnixStream;
+    use zbus::blocking::Proxy;
+    use zbus::proxy::CacheProperties;
+
+    /// Continuous features with a maximum of 100; only brightness is supported.
+    struct Mock(Mutex<u16>);
+
+    impl VcpBackend for Mock {
+        fn get_vcp_value(&self, code: u8) -> crate::Result<VcpValue> {
+            match code {
+                0x10 => Ok(VcpValue::Continuous {
+                    max: 100,
+                    current: *self.0.lock().unwrap(),
+                }),
+                _ => Err(DdcError::from_kind_rc(sys::DDCRC_REPORTED_UNSUPPORTED)),
+            }
+        }
+
+        fn set_vcp_value(&self, code: u8, value: u16) -> crate::Result<()> {
+            match code {
+                0x10 => {
+                    *self.0.lock().unwrap() = value;
+                    Ok(())
+                }
+                _ => Err(DdcError::from_kind_rc(sys::DDCRC_REPORTED_UNSUPPORTED)),
+            }
+        }
+
+        fn get_capabilities_string(&self) -> crate::Result<String> {
+            Ok("(prot(monitor)vcp(10))".to_owned())
+        }
+    }
+
+    impl Backend for Mock {
+        fn get_vcp_table_value(&self, _code: u8) -> crate::Result<Vec<u8>> {
+            Err(DdcError::from_kind_rc(sys::DDCRC_UNIMPLEMENTED))
+        }
+
+        fn get_formatted_vcp_value(&self, code: u8) -> crate::Result<String> {
+            self.get_vcp_value(code).map(|_| "mock".to_owned())
+        }
+    }
+
+    #[test]
+    fn object_paths() {
+        assert_eq!(
+            object_path(DisplayPath::I2C { bus: 4 }).as_str(),
+            "/io/github/libddcutil2/Display/i2c_4"
+        );
+        assert_eq!(
+            object_path(DisplayPath::USB { hiddev_devno: 2 }).as_str(),
+            "/io/github/libddcutil2/Display/hiddev_2"
+        );
+    }
+
+    #[test]
+    fn display_interface() {
+        let object = DisplayObject {
+            backend: Arc::new(Mutex::new(Box::new(Mock(Mutex::new(50))))),
+            capabilities: Arc::default(),
+            display_no: 1,
+            manufacturer: "DEL".to_owned(),
+            model: "DELL U2720Q".to_owned(),
+            serial_number: "ABC123".to_owned(),
+            product_code: 0xa0f4,
+            io_path: "/dev/i2c-4".to_owned(),
+            mccs_version: "2.1".to_owned(),
+            edid: vec![0; 128],
+        };
+        let path = object_path(DisplayPath::I2C { bus: 4 });
+
+        // a peer-to-peer connection over a socket pair stands in for a private bus
+        let (server, client) = UnixStream::pair().unwrap();
+        let server_path = path.clone();
+        let server = std::thread::spawn(move || {
+            zbus::blocking::connection::Builder::async_io_unix_stream(server)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(server_path, object)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = zbus::blocking::connection::Builder::async_io_unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+        let _server = server.join().unwrap();
+
+        let proxy: Proxy = zbus::blocking::proxy::Builder::new(&client)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(path)
+            .unwrap()
+            .interface("io.github.libddcutil2.Display1")
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .unwrap();
+
+        let get = |code: u8| proxy.call::<_, _, (u16, u16)>("GetVcp", &(code,));
+        assert_eq!(get(0x10).unwrap(), (50, 100));
+        proxy.call::<_, _, ()>("SetVcp", &(0x10u8, 70u16)).unwrap();
+        assert_eq!(get(0x10).unwrap(), (70, 100));
+        assert!(get(0x12).is_err());
+        assert!(proxy.call::<_, _, ()>("SetVcp", &(0x12u8, 1u16)).is_err());
+
+        assert_eq!(
+            proxy.get_property::<String>("Model").unwrap(),
+            "DELL U2720Q"
+        );
+        assert_eq!(proxy.get_property::<u16>("ProductCode").unwrap(), 0xa0f4);
+        assert_eq!(proxy.get_property::<Vec<u8>>("Edid").unwrap().len(), 128);
+
+        // the capabilities property is only filled in by GetCapabilities
+        assert_eq!(proxy.get_property::<String>("Capabilities").unwrap(), "");
+        let caps: String = proxy.call("GetCapabilities", &()).unwrap();
+        assert_eq!(caps, "(prot(monitor)vcp(10))");
+        assert_eq!(proxy.get_property::<String>("Capabilities").unwrap(), caps);
+    }
+}
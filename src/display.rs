@@ -1,18 +1,25 @@
+use std::collections::HashMap;
 use std::ffi::CStr;
 use std::mem::MaybeUninit;
 use std::ptr;
 use std::ptr::slice_from_raw_parts;
+use std::sync::Mutex;
 
 use crate::DdcError;
 use crate::DisplayInfo;
+use crate::FeatureFlags;
 use crate::FeatureMetadata;
 use crate::MccsVersion;
+use crate::OwnedDisplayIdentifier;
+use crate::OwnedDisplayInfo;
 use crate::Result;
+use crate::RetryPolicy;
 use crate::capabilities::DisplayCapabilities;
 use crate::sys::DDCA_Non_Table_Vcp_Value;
-use crate::sys::ddca_parse_capabilities_string;
 use crate::sys::{self};
+use crate::take_c_string;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DisplayIdentifier<'a> {
     DisplayNumber(i32),
     I2cBus(i32),
@@ -30,6 +37,39 @@ pub enum DisplayIdentifier<'a> {
     UsbHid(i32),
 }
 
+/// A VCP feature value, decoded according to the MCCS type of the feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VcpValue {
+    /// Continuous feature, with the current value in the range `0..=max`.
+    Continuous { max: u16, current: u16 },
+    /// Non-continuous feature. `sl` holds the value, `sh` may carry extra information depending
+    /// on the feature (e.g. the minor version for 0xDF, or the controller manufacturer for 0xC8).
+    NonContinuous { sh: u8, sl: u8 },
+    /// Table feature.
+    Table(Vec<u8>),
+}
+
+impl VcpValue {
+    /// Decode a non-table value from the library according to the feature flags.
+    ///
+    /// Without flags, the value is decoded as continuous, like before the flags were used.
+    fn from_non_table(val: DDCA_Non_Table_Vcp_Value, flags: Option<FeatureFlags>) -> Self {
+        if flags.is_none_or(|f| f.is_continuous()) {
+            // mh/ml are hi/lo bits of max value, sh/sl are hi/lo bits of current value
+            VcpValue::Continuous {
+                max: u16::from_be_bytes([val.mh, val.ml]),
+                current: u16::from_be_bytes([val.sh, val.sl]),
+            }
+        } else {
+            VcpValue::NonContinuous {
+                sh: val.sh,
+                sl: val.sl,
+            }
+        }
+    }
+}
+
 #[repr(transparent)]
 pub struct TableValue(*mut sys::DDCA_Table_Vcp_Value);
 
@@ -54,20 +94,48 @@ impl Drop for TableValue {
     }
 }
 
+/// Reference to a display detected by the library, which does not need to be opened.
+///
+/// References stay valid until displays are redetected.
 #[repr(transparent)]
-pub(crate) struct SysDisplayRef(pub(crate) sys::DDCA_Display_Ref);
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DisplayRef(pub(crate) sys::DDCA_Display_Ref);
 
-impl SysDisplayRef {
-    #[allow(dead_code)]
+impl DisplayRef {
+    /// Check that the reference is still valid, and optionally that the display is not asleep.
     pub fn validate(&self, require_not_asleep: bool) -> Result<()> {
         unsafe {
             let rc = sys::ddca_validate_display_ref(self.0, require_not_asleep);
             DdcError::check(rc)
         }
     }
+
+    /// Get the display info for the referenced display.
+    pub fn info(&self) -> Result<OwnedDisplayInfo> {
+        let mut info = OwnedDisplayInfo(ptr::null_mut());
+        unsafe {
+            let rc = sys::ddca_get_display_info(self.0, &mut info.0);
+            DdcError::check(rc)?;
+        }
+
+        Ok(info)
+    }
+
+    /// Open the referenced display.
+    pub fn open(&self) -> Result<Display> {
+        Display::from_ref(*self)
+    }
 }
 
-impl std::fmt::Display for SysDisplayRef {
+impl std::fmt::Debug for DisplayRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DisplayRef")
+            .field(&self.to_string())
+            .finish()
+    }
+}
+
+impl std::fmt::Display for DisplayRef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let repr = unsafe { CStr::from_ptr(sys::ddca_dref_repr(self.0)) };
 
@@ -76,7 +144,44 @@ impl std::fmt::Display for SysDisplayRef {
 }
 
 #[repr(transparent)]
-struct SysDisplayIdentifier(sys::DDCA_Display_Identifier);
+pub(crate) struct SysDisplayIdentifier(sys::DDCA_Display_Identifier);
+
+impl SysDisplayIdentifier {
+    /// Create the display identifier for the library.
+    pub(crate) fn new(id: DisplayIdentifier) -> Result<Self> {
+        // SysDisplayIdentifier is an RAII wrapper to make sure the identifier gets freed
+        let mut did = SysDisplayIdentifier(ptr::null_mut());
+        unsafe {
+            let rc = match id {
+                DisplayIdentifier::DisplayNumber(dispno) => {
+                    sys::ddca_create_dispno_display_identifier(dispno, &mut did.0)
+                }
+                DisplayIdentifier::I2cBus(busno) => {
+                    sys::ddca_create_busno_display_identifier(busno, &mut did.0)
+                }
+                DisplayIdentifier::SerialNumber {
+                    manufacturer,
+                    model,
+                    serial,
+                } => sys::ddca_create_mfg_model_sn_display_identifier(
+                    manufacturer.map(|s| s.as_ptr()).unwrap_or(ptr::null()) as *const i8,
+                    model.map(|s| s.as_ptr()).unwrap_or(ptr::null()) as *const i8,
+                    serial.map(|s| s.as_ptr()).unwrap_or(ptr::null()) as *const i8,
+                    &mut did.0,
+                ),
+                DisplayIdentifier::UsbDevice { bus, device } => {
+                    sys::ddca_create_usb_display_identifier(bus, device, &mut did.0)
+                }
+                DisplayIdentifier::UsbHid(dev) => {
+                    sys::ddca_create_usb_hiddev_display_identifier(dev, &mut did.0)
+                }
+            };
+            DdcError::check(rc)?;
+        }
+
+        Ok(did)
+    }
+}
 
 impl Drop for SysDisplayIdentifier {
     fn drop(&mut self) {
@@ -96,58 +201,92 @@ impl std::fmt::Display for SysDisplayIdentifier {
     }
 }
 
-#[derive(Debug)]
+/// Options for [`Display::set_vcp_value_with`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Read the value back after writing it, and check that it matches the requested value.
+    pub verify: bool,
+    /// Maximum difference between the requested and read-back value that is still accepted,
+    /// e.g. for displays that round brightness to steps of 5.
+    pub tolerance: u16,
+    /// Number of times the value is written again if verification fails.
+    pub retries: u32,
+}
+
+impl WriteOptions {
+    /// Verify writes with the given tolerance and number of retries.
+    pub fn verified(tolerance: u16, retries: u32) -> Self {
+        WriteOptions {
+            verify: true,
+            tolerance,
+            retries,
+        }
+    }
+}
+
+/// The write and verify loop of [`Display::set_vcp_value_with`].
+///
+/// Transient errors from `write` or `read` count against the retry policy, values outside the
+/// tolerance against `opts.retries`.
+fn write_verified(
+    policy: &RetryPolicy,
+    value: u16,
+    opts: &WriteOptions,
+    mut write: impl FnMut() -> Result<()>,
+    mut read: impl FnMut() -> Result<u16>,
+) -> Result<u16> {
+    let mut attempts = 0;
+    let mut failures = 0;
+    let mut mismatches = 0;
+    loop {
+        attempts += 1;
+        let result = write().and_then(|()| if opts.verify { read() } else { Ok(value) });
+        match result {
+            Ok(actual) if actual.abs_diff(value) <= opts.tolerance => return Ok(actual),
+            Ok(_) if mismatches < opts.retries => mismatches += 1,
+            Ok(actual) => {
+                return Err(DdcError::from_kind_rc(sys::DDCRC_VERIFY)
+                    .with_attempts(attempts)
+                    .with_read_back(actual));
+            }
+            Err(e) if failures + 1 < policy.max_attempts && policy.is_retryable(&e) => {
+                failures += 1;
+                std::thread::sleep(policy.backoff(failures));
+            }
+            Err(e) => return Err(e.with_attempts(attempts)),
+        }
+    }
+}
+
 pub struct Display {
     dh: sys::DDCA_Display_Handle,
+    retry_policy: RetryPolicy,
+    /// Flags from the feature metadata, looked up once per feature.
+    flags: Mutex<HashMap<sys::DDCA_Vcp_Feature_Code, Option<FeatureFlags>>>,
 }
 
 impl Display {
     /// Construct & open a display from a provided display reference pointer
-    fn from_ref(dref: SysDisplayRef) -> Result<Self> {
+    fn from_ref(dref: DisplayRef) -> Result<Self> {
         let mut dh: sys::DDCA_Display_Handle = ptr::null_mut();
         unsafe {
             let rc = sys::ddca_open_display2(dref.0, false, &mut dh);
             DdcError::check(rc)?;
         }
 
-        Ok(Display { dh })
+        Ok(Display {
+            dh,
+            retry_policy: RetryPolicy::never(),
+            flags: Mutex::new(HashMap::new()),
+        })
     }
 
     /// Construct & open a display from the provided display identifier
     pub fn from_identifier(id: DisplayIdentifier) -> Result<Self> {
-        // create the display identifier for the library
-        // SysDisplayIdentifier is an RAII wrapper to make sure the identifier gets freed
-        let mut did = SysDisplayIdentifier(ptr::null_mut());
-        unsafe {
-            let rc = match id {
-                DisplayIdentifier::DisplayNumber(dispno) => {
-                    sys::ddca_create_dispno_display_identifier(dispno, &mut did.0)
-                }
-                DisplayIdentifier::I2cBus(busno) => {
-                    sys::ddca_create_busno_display_identifier(busno, &mut did.0)
-                }
-                DisplayIdentifier::SerialNumber {
-                    manufacturer,
-                    model,
-                    serial,
-                } => sys::ddca_create_mfg_model_sn_display_identifier(
-                    manufacturer.map(|s| s.as_ptr()).unwrap_or(ptr::null()) as *const i8,
-                    model.map(|s| s.as_ptr()).unwrap_or(ptr::null()) as *const i8,
-                    serial.map(|s| s.as_ptr()).unwrap_or(ptr::null()) as *const i8,
-                    &mut did.0,
-                ),
-                DisplayIdentifier::UsbDevice { bus, device } => {
-                    sys::ddca_create_usb_display_identifier(bus, device, &mut did.0)
-                }
-                DisplayIdentifier::UsbHid(dev) => {
-                    sys::ddca_create_usb_hiddev_display_identifier(dev, &mut did.0)
-                }
-            };
-            DdcError::check(rc)?;
-        }
+        let did = SysDisplayIdentifier::new(id)?;
 
         // Get display ref for the identifier
-        let mut dref = SysDisplayRef(ptr::null_mut());
+        let mut dref = DisplayRef(ptr::null_mut());
         unsafe {
             let rc = sys::ddca_get_display_ref(did.0, &mut dref.0);
             DdcError::check(rc)?;
@@ -156,29 +295,59 @@ impl Display {
         Self::from_ref(dref)
     }
 
+    /// Construct & open a display from the provided owned display identifier
+    pub fn from_owned_identifier(id: &OwnedDisplayIdentifier) -> Result<Self> {
+        id.with_borrowed(Self::from_identifier)?
+    }
+
     /// Construct & open a display from the provided display info
     pub fn from_display_info(info: &DisplayInfo) -> Result<Self> {
         Self::from_ref(info.dref())
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn get_display_ref(&self) -> SysDisplayRef {
-        unsafe { SysDisplayRef(sys::ddca_display_ref_from_handle(self.dh)) }
+    /// Set the policy for retrying VCP reads and writes on this display that fail with transient
+    /// errors. By default operations are not retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
     }
 
-    pub fn get_capabilities(&self) -> Result<DisplayCapabilities> {
-        let mut cap_str = ptr::null_mut();
-        let mut cap_parsed = DisplayCapabilities(ptr::null_mut());
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Get the reference to the display this handle was opened for.
+    pub fn get_display_ref(&self) -> DisplayRef {
+        unsafe { DisplayRef(sys::ddca_display_ref_from_handle(self.dh)) }
+    }
+
+    /// Get the display info for this display.
+    pub fn info(&self) -> Result<OwnedDisplayInfo> {
+        self.get_display_ref().info()
+    }
 
+    /// The library's description of the display handle, from `ddca_dh_repr`.
+    pub fn repr(&self) -> String {
         unsafe {
+            CStr::from_ptr(sys::ddca_dh_repr(self.dh))
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+
+    /// Get the unparsed capabilities string reported by the display.
+    pub fn get_capabilities_string(&self) -> Result<String> {
+        let mut cap_str = ptr::null_mut();
+
+        self.retry_policy.run(|| unsafe {
             let rc = sys::ddca_get_capabilities_string(self.dh, &mut cap_str);
-            DdcError::check(rc)?;
+            DdcError::check(rc)
+        })?;
 
-            let rc = ddca_parse_capabilities_string(cap_str, &mut cap_parsed.0);
-            DdcError::check(rc)?;
-        }
+        Ok(unsafe { take_c_string(cap_str) })
+    }
 
-        Ok(cap_parsed)
+    pub fn get_capabilities(&self) -> Result<DisplayCapabilities> {
+        DisplayCapabilities::parse(&self.get_capabilities_string()?)
     }
 
     pub fn get_mccs_version(&self) -> Result<MccsVersion> {
@@ -209,10 +378,11 @@ impl Display {
         Ok(fm)
     }
 
-    /// Get a 16-bit VCP value.
-    ///
-    /// Return value is a pair of (max value, current value)
-    pub fn get_vcp_value(&self, code: sys::DDCA_Vcp_Feature_Code) -> Result<(u16, u16)> {
+    /// Get the raw bytes of a non-table VCP value, without retrying.
+    fn get_non_table_vcp_value_once(
+        &self,
+        code: sys::DDCA_Vcp_Feature_Code,
+    ) -> Result<DDCA_Non_Table_Vcp_Value> {
         let mut val: MaybeUninit<DDCA_Non_Table_Vcp_Value> = MaybeUninit::uninit();
 
         unsafe {
@@ -220,34 +390,127 @@ impl Display {
             DdcError::check(rc)?;
         }
 
-        let val = unsafe { val.assume_init() };
+        Ok(unsafe { val.assume_init() })
+    }
 
-        // mh/ml are hi/lo bits of max value, sh/sl are hi/lo bits of current value
-        Ok((
-            u16::from_be_bytes([val.mh, val.ml]),
-            u16::from_be_bytes([val.sh, val.sl]),
-        ))
+    /// Get the raw bytes of a non-table VCP value.
+    fn get_non_table_vcp_value(
+        &self,
+        code: sys::DDCA_Vcp_Feature_Code,
+    ) -> Result<DDCA_Non_Table_Vcp_Value> {
+        self.retry_policy
+            .run(|| self.get_non_table_vcp_value_once(code))
     }
 
-    /// Set a 16-bit VCP value
-    pub fn set_vcp_value(&self, code: sys::DDCA_Vcp_Feature_Code, value: u16) -> Result<()> {
+    /// Get a VCP value.
+    ///
+    /// The feature metadata is used to decide how the value is interpreted: continuous features
+    /// report (max, current), non-continuous features report the SH/SL bytes, and table features
+    /// are read as a table. Features without metadata, e.g. manufacturer-specific ones without a
+    /// user-defined feature file, are read as continuous. The metadata is looked up once per
+    /// feature and handle.
+    pub fn get_vcp_value(&self, code: sys::DDCA_Vcp_Feature_Code) -> Result<VcpValue> {
+        let flags = self.feature_flags(code);
+
+        if flags.is_some_and(|f| f.is_table()) {
+            let table = self.get_vcp_table_value(code)?;
+            return Ok(VcpValue::Table(table.as_slice().to_vec()));
+        }
+
+        let val = self.get_non_table_vcp_value(code)?;
+        Ok(VcpValue::from_non_table(val, flags))
+    }
+
+    /// Flags of a feature, if the library has metadata for it.
+    fn feature_flags(&self, code: sys::DDCA_Vcp_Feature_Code) -> Option<FeatureFlags> {
+        let mut cache = self.flags.lock().unwrap_or_else(|e| e.into_inner());
+        *cache
+            .entry(code)
+            .or_insert_with(|| self.get_feature_metadata(code).ok().map(|m| m.flags()))
+    }
+
+    /// Get a VCP value formatted for display, as interpreted by libddcutil.
+    ///
+    /// E.g. for input source (0x60) this gives the name of the input rather than the raw value.
+    pub fn get_formatted_vcp_value(&self, code: sys::DDCA_Vcp_Feature_Code) -> Result<String> {
+        let flags = self.feature_flags(code);
+        let dref = self.get_display_ref();
+        let mut formatted = ptr::null_mut();
+
+        if flags.is_some_and(|f| f.is_table()) {
+            let table = self.get_vcp_table_value(code)?;
+            unsafe {
+                let rc =
+                    sys::ddca_format_table_vcp_value_by_dref(code, dref.0, table.0, &mut formatted);
+                DdcError::check(rc)?;
+            }
+        } else {
+            let mut val = self.get_non_table_vcp_value(code)?;
+            unsafe {
+                let rc = sys::ddca_format_non_table_vcp_value_by_dref(
+                    code,
+                    dref.0,
+                    &mut val,
+                    &mut formatted,
+                );
+                DdcError::check(rc)?;
+            }
+        }
+
+        Ok(unsafe { take_c_string(formatted) })
+    }
+
+    /// Set a 16-bit VCP value, without retrying.
+    fn set_vcp_value_once(&self, code: sys::DDCA_Vcp_Feature_Code, value: u16) -> Result<()> {
         let [hi, lo] = value.to_be_bytes();
 
         unsafe {
             let rc = sys::ddca_set_non_table_vcp_value(self.dh, code, hi, lo);
-            DdcError::check(rc)?;
+            DdcError::check(rc)
         }
-        Ok(())
+    }
+
+    /// Set a 16-bit VCP value
+    pub fn set_vcp_value(&self, code: sys::DDCA_Vcp_Feature_Code, value: u16) -> Result<()> {
+        self.retry_policy
+            .run(|| self.set_vcp_value_once(code, value))
+    }
+
+    /// Set a 16-bit VCP value, optionally reading it back to verify what the display applied.
+    ///
+    /// Returns the value read back from the display, or the requested value if verification is
+    /// disabled. If the read-back value is still outside the tolerance after all retries, this
+    /// fails with [`crate::DdcErrorKind::Verify`], and the value the display reported is
+    /// available from [`DdcError::read_back`], e.g. when the display clamped the value.
+    ///
+    /// Transient errors are retried according to the display's [`RetryPolicy`]. The error
+    /// reports the total number of writes in [`DdcError::attempts`].
+    pub fn set_vcp_value_with(
+        &self,
+        code: sys::DDCA_Vcp_Feature_Code,
+        value: u16,
+        opts: &WriteOptions,
+    ) -> Result<u16> {
+        write_verified(
+            &self.retry_policy,
+            value,
+            opts,
+            || self.set_vcp_value_once(code, value),
+            || {
+                let val = self.get_non_table_vcp_value_once(code)?;
+                Ok(u16::from_be_bytes([val.sh, val.sl]))
+            },
+        )
     }
 
     /// Get a table value.
     pub fn get_vcp_table_value(&self, code: sys::DDCA_Vcp_Feature_Code) -> Result<TableValue> {
         let mut ret = TableValue(ptr::null_mut());
 
-        unsafe {
+        self.retry_policy.run(|| unsafe {
             let rc = sys::ddca_get_table_vcp_value(self.dh, code, &mut ret.0);
-            DdcError::check(rc)?;
-        }
+            DdcError::check(rc)
+        })?;
 
         Ok(ret)
     }
@@ -259,15 +522,17 @@ impl Display {
             bytes: val.as_ptr() as *mut u8,
         };
 
-        unsafe {
+        self.retry_policy.run(|| unsafe {
             let rc = sys::ddca_set_table_vcp_value(self.dh, code, &mut new_val);
-            DdcError::check(rc)?;
-        }
-
-        Ok(())
+            DdcError::check(rc)
+        })
     }
 }
 
+// Safety: libddcutil (2.x) is thread-safe, and a display handle can be used from any thread as
+// long as it is not used concurrently, which `&mut`/`&` borrows of a non-`Sync` type guarantee.
+unsafe impl Send for Display {}
+
 impl Drop for Display {
     fn drop(&mut self) {
         unsafe {
@@ -277,4 +542,83 @@ impl Drop for Display {
     }
 }
 
-// TODO impl Display for Display with ddca_dh_repr?
+impl std::fmt::Display for Display {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.repr())
+    }
+}
+
+impl std::fmt::Debug for Display {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Display")
+            .field("dh", &self.repr())
+            .field("retry_policy", &self.retry_policy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    #[test]
+    fn verified_write_reports_read_back() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::ZERO,
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let writes = Cell::new(0);
+        let write = || {
+            writes.set(writes.get() + 1);
+            Ok(())
+        };
+
+        // a display that clamps to 80
+        let err = write_verified(&policy, 100, &WriteOptions::verified(0, 2), write, || {
+            Ok(80)
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), crate::DdcErrorKind::Verify);
+        assert_eq!(
+            (err.read_back(), err.attempts(), writes.get()),
+            (Some(80), 3, 3)
+        );
+
+        // a transient read error is retried once, within the same loop
+        writes.set(0);
+        let reads = Cell::new(0);
+        let value = write_verified(&policy, 100, &WriteOptions::verified(5, 0), write, || {
+            reads.set(reads.get() + 1);
+            match reads.get() {
+                1 => Err(DdcError::from_kind_rc(sys::DDCRC_NULL_RESPONSE)),
+                _ => Ok(98),
+            }
+        });
+        assert_eq!((value.unwrap(), writes.get()), (98, 2));
+    }
+
+    #[test]
+    fn non_table_values() {
+        let val = DDCA_Non_Table_Vcp_Value {
+            mh: 0,
+            ml: 100,
+            sh: 0,
+            sl: 0x0f,
+        };
+        assert_eq!(
+            VcpValue::from_non_table(val, Some(FeatureFlags::RW | FeatureFlags::SIMPLE_NC)),
+            VcpValue::NonContinuous { sh: 0, sl: 0x0f }
+        );
+        // without metadata, e.g. for manufacturer-specific features
+        assert_eq!(
+            VcpValue::from_non_table(val, None),
+            VcpValue::Continuous {
+                max: 100,
+                current: 0x0f
+            }
+        );
+    }
+}
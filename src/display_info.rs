@@ -2,43 +2,88 @@ use core::str;
 use std::slice::SliceIndex;
 
 use crate::MccsVersion;
-use crate::display::SysDisplayRef;
+use crate::OwnedDisplayIdentifier;
+use crate::display::DisplayRef;
 use crate::err::*;
 use crate::str_field_getter;
 use crate::sys;
 
 /// Location of the display.
 #[non_exhaustive]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DisplayPath {
     I2C { bus: i32 },
     USB { hiddev_devno: i32 },
 }
 
-#[repr(transparent)]
-pub struct DisplayInfo(sys::DDCA_Display_Info);
-
-impl DisplayInfo {
-    pub fn display_no(&self) -> i32 {
-        self.0.dispno as i32
-    }
-
-    pub fn path(&self) -> DisplayPath {
-        let p = self.0.path;
+impl From<sys::DDCA_IO_Path> for DisplayPath {
+    fn from(p: sys::DDCA_IO_Path) -> Self {
         match p.io_mode {
             sys::DDCA_IO_Mode::DDCA_IO_I2C => DisplayPath::I2C {
                 bus: unsafe { p.path.i2c_busno },
             },
             sys::DDCA_IO_Mode::DDCA_IO_USB => DisplayPath::USB {
-                // TODO should this include the usb_devuce/bus fields of the display_info?
+                // the USB bus/device are only in the display info, see `DisplayInfo::usb_location`
                 hiddev_devno: unsafe { p.path.hiddev_devno },
             },
             #[allow(unreachable_patterns)]
             _ => panic!("Unknown IO mode {0:?}", p.io_mode),
         }
     }
+}
+
+/// Location of a display controlled over USB HID rather than I2C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UsbLocation {
+    pub bus: i32,
+    pub device: i32,
+    /// Number of the `/dev/usb/hiddevN` device.
+    pub hiddev_devno: i32,
+}
+
+#[repr(transparent)]
+pub struct DisplayInfo(sys::DDCA_Display_Info);
+
+impl DisplayInfo {
+    pub fn display_no(&self) -> i32 {
+        self.0.dispno as i32
+    }
+
+    pub fn path(&self) -> DisplayPath {
+        DisplayPath::from(self.0.path)
+    }
+
+    /// Full USB location, for displays controlled over USB.
+    pub fn usb_location(&self) -> Option<UsbLocation> {
+        match self.path() {
+            DisplayPath::USB { hiddev_devno } => Some(UsbLocation {
+                bus: self.usb_bus(),
+                device: self.usb_device(),
+                hiddev_devno,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Identifier for this display by its location: the I2C bus, or the hiddev device for
+    /// displays controlled over USB. See [`usb_location`](Self::usb_location) for the USB bus
+    /// and device.
+    pub fn identifier(&self) -> OwnedDisplayIdentifier {
+        match self.path() {
+            DisplayPath::I2C { bus } => OwnedDisplayIdentifier::I2cBus(bus),
+            DisplayPath::USB { hiddev_devno } => OwnedDisplayIdentifier::UsbHid(hiddev_devno),
+        }
+    }
+
+    /// USB bus number, for displays controlled over USB.
+    pub fn usb_bus(&self) -> i32 {
+        self.0.usb_bus
+    }
 
-    // TODO usb_bus/usb_device?
+    /// USB device number, for displays controlled over USB.
+    pub fn usb_device(&self) -> i32 {
+        self.0.usb_device
+    }
 
     str_field_getter!(manufacturer, mfg_id);
 
@@ -50,14 +95,59 @@ impl DisplayInfo {
         self.0.product_code
     }
 
-    // TODO expose edid_bytes?
+    /// The raw 128-byte EDID of the display.
+    pub fn edid_bytes(&self) -> &[u8; 128] {
+        &self.0.edid_bytes
+    }
 
     pub fn vcp_version(&self) -> MccsVersion {
         self.0.vcp_version
     }
 
-    pub(crate) fn dref(&self) -> crate::display::SysDisplayRef {
-        SysDisplayRef(self.0.dref)
+    /// Reference to the display, e.g. for opening it.
+    pub fn dref(&self) -> DisplayRef {
+        DisplayRef(self.0.dref)
+    }
+}
+
+impl std::fmt::Debug for DisplayInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DisplayInfo")
+            .field("display_no", &self.display_no())
+            .field("path", &self.path())
+            .field("manufacturer", &self.manufacturer())
+            .field("model", &self.model())
+            .field("serial_number", &self.serial_number())
+            .field("product_code", &self.product_code())
+            .field("vcp_version", &self.vcp_version())
+            .finish()
+    }
+}
+
+/// Display info for a single display, which is not part of a [`DisplayInfoList`].
+///
+/// Dereferences to [`DisplayInfo`].
+pub struct OwnedDisplayInfo(pub(crate) *mut sys::DDCA_Display_Info);
+
+impl std::ops::Deref for OwnedDisplayInfo {
+    type Target = DisplayInfo;
+
+    fn deref(&self) -> &DisplayInfo {
+        unsafe { &*(self.0 as *const DisplayInfo) }
+    }
+}
+
+impl std::fmt::Debug for OwnedDisplayInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl Drop for OwnedDisplayInfo {
+    fn drop(&mut self) {
+        unsafe {
+            sys::ddca_free_display_info(self.0);
+        }
     }
 }
 
@@ -110,3 +200,61 @@ pub fn get_display_info_list(include_invalid_displays: bool) -> Result<DisplayIn
 
     Ok(DisplayInfoList(ret))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn io_path(io_mode: sys::DDCA_IO_Mode, n: i32) -> sys::DDCA_IO_Path {
+        sys::DDCA_IO_Path {
+            io_mode,
+            path: sys::DDCA_IO_Path__bindgen_ty_1 { i2c_busno: n },
+        }
+    }
+
+    fn info(path: sys::DDCA_IO_Path, usb_bus: i32, usb_device: i32) -> DisplayInfo {
+        let mut info: sys::DDCA_Display_Info = unsafe { std::mem::zeroed() };
+        info.dispno = 1;
+        info.path = path;
+        info.usb_bus = usb_bus;
+        info.usb_device = usb_device;
+        DisplayInfo(info)
+    }
+
+    #[test]
+    fn test_io_path() {
+        assert_eq!(
+            DisplayPath::from(io_path(sys::DDCA_IO_Mode::DDCA_IO_I2C, 6)),
+            DisplayPath::I2C { bus: 6 }
+        );
+        assert_eq!(
+            DisplayPath::from(io_path(sys::DDCA_IO_Mode::DDCA_IO_USB, 3)),
+            DisplayPath::USB { hiddev_devno: 3 }
+        );
+    }
+
+    #[test]
+    fn test_i2c_location() {
+        let info = info(io_path(sys::DDCA_IO_Mode::DDCA_IO_I2C, 6), 0, 0);
+        assert_eq!(info.usb_location(), None);
+        assert_eq!(info.identifier(), OwnedDisplayIdentifier::I2cBus(6));
+        assert!(info.identifier().matches(&info));
+    }
+
+    #[test]
+    fn test_usb_location() {
+        let info = info(io_path(sys::DDCA_IO_Mode::DDCA_IO_USB, 3), 1, 4);
+        assert_eq!(
+            info.usb_location(),
+            Some(UsbLocation {
+                bus: 1,
+                device: 4,
+                hiddev_devno: 3
+            })
+        );
+        assert_eq!(info.identifier(), OwnedDisplayIdentifier::UsbHid(3));
+        assert!(info.identifier().matches(&info));
+        assert!(OwnedDisplayIdentifier::UsbDevice { bus: 1, device: 4 }.matches(&info));
+        assert!(!OwnedDisplayIdentifier::UsbDevice { bus: 1, device: 5 }.matches(&info));
+    }
+}
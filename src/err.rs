@@ -5,6 +5,138 @@ use std::{borrow::Cow, ffi::CStr, fmt::Display, ptr::null_mut};
 /// Results from this crate always use `DdcError` as errors
 pub type Result<T> = std::result::Result<T, DdcError>;
 
+/// Classification of the status codes returned by the DDC library.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DdcErrorKind {
+    /// DDC data error
+    DdcData,
+    /// Null response from the display
+    NullResponse,
+    /// Received a fragment of a multi-part response
+    MultiPartReadFragment,
+    /// All retries returned a response of all zeroes
+    AllTriesZero,
+    /// The display reported that the feature is unsupported
+    ReportedUnsupported,
+    /// Read returned all zeroes
+    ReadAllZero,
+    /// Invalid byte count
+    BadByteCount,
+    /// The bytes read were identical to the bytes written
+    ReadEqualsWrite,
+    /// Invalid mode
+    InvalidMode,
+    /// Maximum retries exceeded
+    Retries,
+    /// Error reading the EDID
+    Edid,
+    /// The library determined that the feature is unsupported
+    DeterminedUnsupported,
+    /// Invalid argument
+    InvalidArgument,
+    /// Invalid operation
+    InvalidOperation,
+    /// Not implemented
+    Unimplemented,
+    /// The library was not initialized
+    Uninitialized,
+    /// Unknown feature code
+    UnknownFeature,
+    /// Failed to interpret a value
+    InterpretationFailed,
+    /// Multiple features failed
+    MultiFeatureError,
+    /// Invalid display
+    InvalidDisplay,
+    /// Internal error in the library
+    InternalError,
+    /// Other error
+    Other,
+    /// The value read back after a write did not match
+    Verify,
+    /// Not found
+    NotFound,
+    /// The display is locked by another thread
+    Locked,
+    /// The display is already open
+    AlreadyOpen,
+    /// Invalid data
+    BadData,
+    /// Invalid configuration file
+    InvalidConfigFile,
+    /// The display was disconnected
+    Disconnected,
+    /// The display is in a DPMS sleep mode
+    DpmsAsleep,
+    /// The display is locked by another process
+    Flocked,
+    /// The operation was blocked because display detection is in progress
+    Quiesced,
+    /// A system error, represented as a negative `errno` value
+    Errno(i32),
+}
+
+impl DdcErrorKind {
+    /// Classify a status code returned by the library.
+    pub fn from_rc(rc: i32) -> Self {
+        match rc {
+            sys::DDCRC_DDC_DATA => Self::DdcData,
+            sys::DDCRC_NULL_RESPONSE => Self::NullResponse,
+            sys::DDCRC_MULTI_PART_READ_FRAGMENT => Self::MultiPartReadFragment,
+            sys::DDCRC_ALL_TRIES_ZERO => Self::AllTriesZero,
+            sys::DDCRC_REPORTED_UNSUPPORTED => Self::ReportedUnsupported,
+            sys::DDCRC_READ_ALL_ZERO => Self::ReadAllZero,
+            sys::DDCRC_BAD_BYTECT => Self::BadByteCount,
+            sys::DDCRC_READ_EQUALS_WRITE => Self::ReadEqualsWrite,
+            sys::DDCRC_INVALID_MODE => Self::InvalidMode,
+            sys::DDCRC_RETRIES => Self::Retries,
+            sys::DDCRC_EDID => Self::Edid,
+            sys::DDCRC_DETERMINED_UNSUPPORTED => Self::DeterminedUnsupported,
+            sys::DDCRC_ARG => Self::InvalidArgument,
+            sys::DDCRC_INVALID_OPERATION => Self::InvalidOperation,
+            sys::DDCRC_UNIMPLEMENTED => Self::Unimplemented,
+            sys::DDCRC_UNINITIALIZED => Self::Uninitialized,
+            sys::DDCRC_UNKNOWN_FEATURE => Self::UnknownFeature,
+            sys::DDCRC_INTERPRETATION_FAILED => Self::InterpretationFailed,
+            sys::DDCRC_MULTI_FEATURE_ERROR => Self::MultiFeatureError,
+            sys::DDCRC_INVALID_DISPLAY => Self::InvalidDisplay,
+            sys::DDCRC_INTERNAL_ERROR => Self::InternalError,
+            sys::DDCRC_OTHER => Self::Other,
+            sys::DDCRC_VERIFY => Self::Verify,
+            sys::DDCRC_NOT_FOUND => Self::NotFound,
+            sys::DDCRC_LOCKED => Self::Locked,
+            sys::DDCRC_ALREADY_OPEN => Self::AlreadyOpen,
+            sys::DDCRC_BAD_DATA => Self::BadData,
+            sys::DDCRC_INVALID_CONFIG_FILE => Self::InvalidConfigFile,
+            sys::DDCRC_DISCONNECTED => Self::Disconnected,
+            sys::DDCRC_DPMS_ASLEEP => Self::DpmsAsleep,
+            sys::DDCRC_FLOCKED => Self::Flocked,
+            sys::DDCRC_QUIESCED => Self::Quiesced,
+            errno => Self::Errno(errno),
+        }
+    }
+
+    /// Whether this kind of error is usually caused by flaky DDC/CI communication, and may
+    /// succeed if the operation is retried.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::DdcData
+                | Self::NullResponse
+                | Self::MultiPartReadFragment
+                | Self::AllTriesZero
+                | Self::ReadAllZero
+                | Self::BadByteCount
+                | Self::ReadEqualsWrite
+                | Self::Retries
+                | Self::Locked
+                | Self::Flocked
+                | Self::Quiesced
+        )
+    }
+}
+
 /// Errors from the DDC library.
 #[derive(Debug)]
 pub struct DdcError {
@@ -12,6 +144,8 @@ pub struct DdcError {
     name: &'static CStr,
     desc: &'static CStr,
     detail: *mut sys::DDCA_Error_Detail,
+    attempts: u32,
+    read_back: Option<u16>,
 }
 
 impl DdcError {
@@ -37,10 +171,53 @@ impl DdcError {
                 name,
                 desc,
                 detail,
+                attempts: 1,
+                read_back: None,
             }
         }
     }
 
+    /// Construct a `DdcError` for a failure detected by this crate rather than the library.
+    pub(crate) fn from_kind_rc(rc: i32) -> Self {
+        // Safety: only used with the `DDCRC_*` constants
+        unsafe { Self::from_rc(rc) }
+    }
+
+    /// Record the number of attempts made before giving up with this error.
+    pub(crate) fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Record the value read back from the display when verifying a write failed.
+    pub(crate) fn with_read_back(mut self, value: u16) -> Self {
+        self.read_back = Some(value);
+        self
+    }
+
+    /// The raw status code.
+    pub fn rc(&self) -> i32 {
+        self.rc
+    }
+
+    /// The kind of error.
+    pub fn kind(&self) -> DdcErrorKind {
+        DdcErrorKind::from_rc(self.rc)
+    }
+
+    /// Number of attempts made before the operation failed with this error.
+    ///
+    /// This is 1 unless the operation was retried with a [`crate::RetryPolicy`].
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// For a failed verification (`DDCRC_VERIFY`) from [`crate::Display::set_vcp_value_with`],
+    /// the value the display last reported, e.g. because it clamped the requested value.
+    pub fn read_back(&self) -> Option<u16> {
+        self.read_back
+    }
+
     /// Convenience function for wrapping functions from `sys` that return error codes, to convert
     /// the result to `Result`.
     ///
@@ -65,6 +242,9 @@ impl DdcError {
     }
 }
 
+// Safety: the error detail is owned by the error, and only freed when it is dropped.
+unsafe impl Send for DdcError {}
+
 impl Drop for DdcError {
     fn drop(&mut self) {
         if !self.detail.is_null() {
@@ -78,13 +258,20 @@ impl Drop for DdcError {
 impl Display for DdcError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // Main error
-        writeln!(
+        write!(
             f,
             "DDC Error: {0} ({1}): {2}",
             self.name.to_string_lossy(),
             self.rc,
             self.desc.to_string_lossy(),
         )?;
+        if self.attempts > 1 {
+            write!(f, " (after {0} attempts)", self.attempts)?;
+        }
+        if let Some(value) = self.read_back {
+            write!(f, " (read back {value})")?;
+        }
+        writeln!(f)?;
 
         // Details if present
         if self.detail.is_null() {
@@ -133,7 +320,7 @@ impl std::error::Error for DdcError {}
 
 /// Custom way to convert DdcError to anyhow error.
 ///
-/// This is needed because these errors are not Send/Sync,
+/// This is needed because these errors are not Sync,
 /// so this can't be implemented automatically by anyhow,
 /// but also we can't implement From<DdcError> for anyhow
 /// because it could provide an implementation in the future...
@@ -178,6 +365,8 @@ mod test {
                 name: c"test",
                 desc: c"error for testing",
                 detail: ptr::null_mut(),
+                attempts: 1,
+                read_back: None,
             })
             .anyhow()
         }
@@ -0,0 +1,151 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::display_info::DisplayPath;
+use crate::err::{DdcError, Result};
+use crate::sys;
+
+/// Type of a display status change reported by the library.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DisplayEventType {
+    Connected,
+    Disconnected,
+    /// The display woke up from a DPMS sleep mode.
+    Awake,
+    /// The display entered a DPMS sleep mode.
+    Asleep,
+    /// DDC communication became possible on a connected display.
+    DdcEnabled,
+}
+
+/// A change in the status of a display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayEvent {
+    /// Time of the event, from the monotonic clock.
+    pub timestamp_nanos: u64,
+    pub event_type: DisplayEventType,
+    pub path: DisplayPath,
+}
+
+impl DisplayEvent {
+    fn from_sys(event: &sys::DDCA_Display_Status_Event) -> Option<Self> {
+        use sys::DDCA_Display_Event_Type as T;
+
+        let event_type = match event.event_type {
+            T::DDCA_EVENT_DISPLAY_CONNECTED => DisplayEventType::Connected,
+            T::DDCA_EVENT_DISPLAY_DISCONNECTED => DisplayEventType::Disconnected,
+            T::DDCA_EVENT_DPMS_AWAKE => DisplayEventType::Awake,
+            T::DDCA_EVENT_DPMS_ASLEEP => DisplayEventType::Asleep,
+            T::DDCA_EVENT_DDC_ENABLED => DisplayEventType::DdcEnabled,
+            #[allow(unreachable_patterns)]
+            _ => return None,
+        };
+
+        Some(DisplayEvent {
+            timestamp_nanos: event.timestamp_nanos,
+            event_type,
+            path: DisplayPath::from(event.io_path),
+        })
+    }
+}
+
+/// Classes of events to watch for with [`start_watch_displays`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WatchClasses {
+    /// DPMS sleep/wake events
+    Dpms,
+    /// Connect/disconnect events
+    Connection,
+    All,
+}
+
+impl From<WatchClasses> for sys::DDCA_Display_Event_Class {
+    fn from(val: WatchClasses) -> Self {
+        match val {
+            WatchClasses::Dpms => sys::DDCA_Display_Event_Class::DDCA_EVENT_CLASS_DPMS,
+            WatchClasses::Connection => {
+                sys::DDCA_Display_Event_Class::DDCA_EVENT_CLASS_DISPLAY_CONNECTION
+            }
+            WatchClasses::All => sys::DDCA_Display_Event_Class::DDCA_EVENT_CLASS_ALL,
+        }
+    }
+}
+
+/// Start the library's thread that watches for display changes.
+///
+/// Callbacks registered with [`subscribe`] are only called while displays are being watched.
+pub fn start_watch_displays(classes: WatchClasses) -> Result<()> {
+    unsafe {
+        let rc = sys::ddca_start_watch_displays(classes.into());
+        DdcError::check(rc)
+    }
+}
+
+/// Stop watching for display changes, optionally waiting for the watch thread to finish.
+pub fn stop_watch_displays(wait: bool) -> Result<()> {
+    unsafe {
+        let rc = sys::ddca_stop_watch_displays(wait);
+        DdcError::check(rc)
+    }
+}
+
+type Callback = Box<dyn Fn(&DisplayEvent) + Send + Sync>;
+
+/// Callbacks to invoke on display events. The library callback only takes the event, so
+/// closures are kept here and called from a single registered function.
+static CALLBACKS: Mutex<Vec<(u64, Callback)>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+unsafe extern "C" fn dispatch_event(event: sys::DDCA_Display_Status_Event) {
+    let Some(event) = DisplayEvent::from_sys(&event) else {
+        return;
+    };
+
+    if let Ok(callbacks) = CALLBACKS.lock() {
+        for (_, cb) in callbacks.iter() {
+            cb(&event);
+        }
+    }
+}
+
+/// Registration of a display event callback. The callback is unregistered when this is dropped.
+#[derive(Debug)]
+#[must_use = "the callback is unregistered when the subscription is dropped"]
+pub struct EventSubscription(u64);
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        let mut callbacks = CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+        callbacks.retain(|(id, _)| *id != self.0);
+
+        if callbacks.is_empty() {
+            unsafe {
+                // ignore errors when unregistering
+                let _rc = sys::ddca_unregister_display_status_callback(Some(dispatch_event));
+            }
+        }
+    }
+}
+
+/// Register a callback for display events.
+///
+/// The callback runs on the library's watch thread. It must not call `subscribe` or drop an
+/// `EventSubscription`, since that would deadlock.
+pub fn subscribe(
+    callback: impl Fn(&DisplayEvent) + Send + Sync + 'static,
+) -> Result<EventSubscription> {
+    let mut callbacks = CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+
+    if callbacks.is_empty() {
+        unsafe {
+            let rc = sys::ddca_register_display_status_callback(Some(dispatch_event));
+            DdcError::check(rc)?;
+        }
+    }
+
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    callbacks.push((id, Box::new(callback)));
+
+    Ok(EventSubscription(id))
+}
@@ -0,0 +1,386 @@
+//! Prometheus/OpenMetrics exporter for monitor state.
+//!
+//! An [`Exporter`] periodically reads a set of features from every display and serves them, with
+//! the latency of the DDC calls and error counts by [`DdcErrorKind`], in the Prometheus text
+//! format on `/metrics`.
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::display::{Display, VcpValue};
+use crate::display_info::{DisplayInfo, DisplayPath, get_display_info_list};
+use crate::err::{DdcError, DdcErrorKind};
+
+/// Features read when none are configured: brightness, contrast, power mode and usage hours.
+pub const DEFAULT_FEATURES: &[u8] = &[0x10, 0x12, 0xD6, 0xC0];
+
+/// Upper bounds of the DDC call latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Display usage time, reported in hours across all four value bytes.
+const USAGE_TIME: u8 = 0xC0;
+
+/// A value as exported: the current value, and the maximum for continuous features.
+fn sample(code: u8, value: &VcpValue) -> Option<(f64, Option<f64>)> {
+    match *value {
+        VcpValue::Continuous { max, current } if code == USAGE_TIME => {
+            Some(((u32::from(max) << 16 | u32::from(current)) as f64, None))
+        }
+        VcpValue::Continuous { max, current } => Some((current as f64, Some(max as f64))),
+        VcpValue::NonContinuous { sl, .. } => Some((sl as f64, None)),
+        VcpValue::Table(_) => None,
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Labels identifying a display in the exported metrics.
+fn display_labels(info: &DisplayInfo) -> String {
+    let path = match info.path() {
+        DisplayPath::I2C { bus } => format!("/dev/i2c-{bus}"),
+        DisplayPath::USB { hiddev_devno } => format!("/dev/usb/hiddev{hiddev_devno}"),
+    };
+
+    format!(
+        "display=\"{0}\",path=\"{1}\",manufacturer=\"{2}\",model=\"{3}\",serial=\"{4}\"",
+        info.display_no(),
+        path,
+        escape(info.manufacturer()),
+        escape(info.model()),
+        escape(info.serial_number()),
+    )
+}
+
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        self.buckets.resize(LATENCY_BUCKETS.len(), 0);
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if secs <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        self.buckets.resize(LATENCY_BUCKETS.len(), 0);
+        for (total, new) in self.buckets.iter_mut().zip(&other.buckets) {
+            *total += new;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+}
+
+/// The state of one display at the last poll.
+#[derive(Debug, Clone)]
+struct DisplaySample {
+    labels: String,
+    up: bool,
+    values: Vec<(u8, VcpValue)>,
+}
+
+/// Collected metrics, rendered with `Display` in the Prometheus text format.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    displays: Vec<DisplaySample>,
+    errors: BTreeMap<String, u64>,
+    latency: Histogram,
+    polls: u64,
+}
+
+impl Metrics {
+    /// Time a DDC call, and count its error if it fails. Unsupported features are not errors.
+    fn record<T>(&mut self, f: impl FnOnce() -> crate::Result<T>) -> crate::Result<T> {
+        let start = Instant::now();
+        let ret = f();
+        self.latency.observe(start.elapsed().as_secs_f64());
+        if let Err(e) = &ret
+            && !is_unsupported(e)
+        {
+            self.record_error(e.kind());
+        }
+        ret
+    }
+
+    fn record_error(&mut self, kind: DdcErrorKind) {
+        *self.errors.entry(format!("{kind:?}")).or_default() += 1;
+    }
+
+    /// Total number of errors of the given kind so far.
+    pub fn errors(&self, kind: DdcErrorKind) -> u64 {
+        self.errors
+            .get(&format!("{kind:?}"))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "# HELP ddc_up Whether the display answered on the last poll."
+        )?;
+        writeln!(f, "# TYPE ddc_up gauge")?;
+        for d in &self.displays {
+            writeln!(f, "ddc_up{{{0}}} {1}", d.labels, u8::from(d.up))?;
+        }
+
+        writeln!(
+            f,
+            "# HELP ddc_vcp_value Current value of a VCP feature. Usage time (0xc0) is in hours."
+        )?;
+        writeln!(f, "# TYPE ddc_vcp_value gauge")?;
+        let mut maxima = String::new();
+        for d in &self.displays {
+            for (code, value) in &d.values {
+                let Some((current, max)) = sample(*code, value) else {
+                    continue;
+                };
+                let name = crate::feature_name(*code).unwrap_or_default();
+                let labels = format!(
+                    "{0},feature=\"0x{code:02x}\",name=\"{1}\"",
+                    d.labels,
+                    escape(name)
+                );
+                writeln!(f, "ddc_vcp_value{{{labels}}} {current}")?;
+                if let Some(max) = max {
+                    let _ = writeln!(maxima, "ddc_vcp_max{{{labels}}} {max}");
+                }
+            }
+        }
+
+        writeln!(
+            f,
+            "# HELP ddc_vcp_max Maximum value of a continuous VCP feature."
+        )?;
+        writeln!(f, "# TYPE ddc_vcp_max gauge")?;
+        f.write_str(&maxima)?;
+
+        writeln!(f, "# HELP ddc_call_duration_seconds Latency of DDC calls.")?;
+        writeln!(f, "# TYPE ddc_call_duration_seconds histogram")?;
+        let buckets = self.latency.buckets.iter().chain(std::iter::repeat(&0));
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(buckets) {
+            writeln!(
+                f,
+                "ddc_call_duration_seconds_bucket{{le=\"{bound}\"}} {count}"
+            )?;
+        }
+        writeln!(
+            f,
+            "ddc_call_duration_seconds_bucket{{le=\"+Inf\"}} {0}",
+            self.latency.count
+        )?;
+        writeln!(f, "ddc_call_duration_seconds_sum {0}", self.latency.sum)?;
+        writeln!(f, "ddc_call_duration_seconds_count {0}", self.latency.count)?;
+
+        writeln!(f, "# HELP ddc_errors_total DDC errors by kind.")?;
+        writeln!(f, "# TYPE ddc_errors_total counter")?;
+        for (kind, count) in &self.errors {
+            writeln!(f, "ddc_errors_total{{kind=\"{kind}\"}} {count}")?;
+        }
+
+        writeln!(
+            f,
+            "# HELP ddc_polls_total Number of times the displays were polled."
+        )?;
+        writeln!(f, "# TYPE ddc_polls_total counter")?;
+        writeln!(f, "ddc_polls_total {0}", self.polls)
+    }
+}
+
+/// Polls displays and serves the results over HTTP.
+#[derive(Debug)]
+pub struct Exporter {
+    features: Vec<u8>,
+    interval: Duration,
+    metrics: Mutex<Metrics>,
+}
+
+impl Exporter {
+    pub fn new(features: Vec<u8>, interval: Duration) -> Arc<Self> {
+        Arc::new(Exporter {
+            features,
+            interval,
+            metrics: Mutex::new(Metrics::default()),
+        })
+    }
+
+    /// A copy of the current metrics.
+    pub fn metrics(&self) -> Metrics {
+        self.lock_metrics().clone()
+    }
+
+    fn lock_metrics(&self) -> MutexGuard<'_, Metrics> {
+        self.metrics.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Read the configured features from every display.
+    ///
+    /// Features a display does not support are left out rather than failing the poll.
+    pub fn poll(&self) -> crate::Result<()> {
+        let list = get_display_info_list(false).inspect_err(|e| {
+            self.lock_metrics().record_error(e.kind());
+        })?;
+
+        // collect without holding the lock, so scrapes are not blocked by slow displays
+        let mut scratch = Metrics::default();
+        let displays: Vec<_> = list
+            .as_slice()
+            .iter()
+            .map(|info| self.poll_display(info, &mut scratch))
+            .collect();
+
+        let mut metrics = self.lock_metrics();
+        metrics.displays = displays;
+        metrics.polls += 1;
+        for (kind, count) in scratch.errors {
+            *metrics.errors.entry(kind).or_default() += count;
+        }
+        metrics.latency.merge(&scratch.latency);
+
+        Ok(())
+    }
+
+    fn poll_display(&self, info: &DisplayInfo, metrics: &mut Metrics) -> DisplaySample {
+        let mut sample = DisplaySample {
+            labels: display_labels(info),
+            up: false,
+            values: Vec::new(),
+        };
+
+        let display = match metrics.record(|| Display::from_display_info(info)) {
+            Ok(d) => d,
+            Err(_) => return sample,
+        };
+
+        for &code in &self.features {
+            match metrics.record(|| display.get_vcp_value(code)) {
+                Ok(value) => sample.values.push((code, value)),
+                Err(e) if is_unsupported(&e) => {}
+                Err(_) => return sample,
+            }
+        }
+
+        sample.up = true;
+        sample
+    }
+
+    /// Poll every `interval` on a background thread, and serve `/metrics` on `listener`.
+    ///
+    /// This only returns if accepting connections fails.
+    pub fn run(self: &Arc<Self>, listener: &TcpListener) -> io::Result<()> {
+        let poller = Arc::downgrade(self);
+        let interval = self.interval;
+        std::thread::spawn(move || {
+            while let Some(exporter) = poller.upgrade() {
+                // errors are counted in the metrics
+                let _ = exporter.poll();
+                drop(exporter);
+                std::thread::sleep(interval);
+            }
+        });
+
+        for stream in listener.incoming() {
+            // a broken client connection only affects that client
+            let _ = self.serve_http(stream?);
+        }
+        Ok(())
+    }
+
+    fn serve_http(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut request = String::new();
+        BufReader::new(&stream).read_line(&mut request)?;
+
+        let mut parts = request.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some(path)) if path.split('?').next() == Some("/metrics") => {
+                ("200 OK", self.metrics().to_string())
+            }
+            (Some("GET"), _) => ("404 Not Found", "not found\n".to_owned()),
+            _ => ("405 Method Not Allowed", "method not allowed\n".to_owned()),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\n\
+             Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+             Content-Length: {0}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+}
+
+/// Whether the error means the display does not support the feature, rather than a failure.
+fn is_unsupported(e: &DdcError) -> bool {
+    matches!(
+        e.kind(),
+        DdcErrorKind::ReportedUnsupported | DdcErrorKind::DeterminedUnsupported
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let mut metrics = Metrics {
+            displays: vec![DisplaySample {
+                labels: "display=\"1\"".to_owned(),
+                up: true,
+                values: vec![
+                    (
+                        0x10,
+                        VcpValue::Continuous {
+                            max: 100,
+                            current: 70,
+                        },
+                    ),
+                    (0xD6, VcpValue::NonContinuous { sh: 0, sl: 4 }),
+                    (0xC0, VcpValue::Continuous { max: 1, current: 2 }),
+                ],
+            }],
+            ..Default::default()
+        };
+        metrics.latency.observe(0.04);
+        metrics.record_error(DdcErrorKind::NullResponse);
+        metrics.record_error(DdcErrorKind::NullResponse);
+
+        let text = metrics.to_string();
+        assert!(text.contains("ddc_up{display=\"1\"} 1\n"));
+        assert!(text.contains("ddc_vcp_max{display=\"1\",feature=\"0x10\""));
+        let value = |code: &str| {
+            text.lines()
+                .find(|l| l.starts_with("ddc_vcp_value") && l.contains(code))
+                .and_then(|l| l.rsplit(' ').next())
+                .map(str::to_owned)
+        };
+        assert_eq!(value("0xd6").as_deref(), Some("4"));
+        assert_eq!(value("0xc0").as_deref(), Some("65538"));
+        assert!(!text.contains("ddc_vcp_max{display=\"1\",feature=\"0xc0\""));
+        assert!(text.contains("ddc_call_duration_seconds_bucket{le=\"0.05\"} 1\n"));
+        assert!(text.contains("ddc_call_duration_seconds_bucket{le=\"0.025\"} 0\n"));
+        assert!(text.contains("ddc_errors_total{kind=\"NullResponse\"} 2\n"));
+        assert_eq!(metrics.errors(DdcErrorKind::NullResponse), 2);
+    }
+}
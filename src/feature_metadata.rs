@@ -2,9 +2,90 @@ use std::{ffi::CStr, slice};
 
 use crate::{
     MccsVersion,
+    capabilities::DisplayCapabilities,
     sys::{self},
 };
 
+bitflags::bitflags! {
+    /// Flags describing how a VCP feature can be accessed and how its value is interpreted.
+    ///
+    /// Exactly one of `RO`, `WO`, `RW` is set, and exactly one of the type flags
+    /// (`STD_CONT`, `COMPLEX_CONT`, `SIMPLE_NC`, ...) is set.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct FeatureFlags: u16 {
+        /// Read-only feature
+        const RO = sys::DDCA_RO as u16;
+        /// Write-only feature
+        const WO = sys::DDCA_WO as u16;
+        /// Feature is both readable and writable
+        const RW = Self::RO.bits() | Self::WO.bits();
+
+        /// Normal continuous feature
+        const STD_CONT = sys::DDCA_STD_CONT as u16;
+        /// Continuous feature with special interpretation
+        const COMPLEX_CONT = sys::DDCA_COMPLEX_CONT as u16;
+        /// Non-continuous feature with a defined list of values in byte SL
+        const SIMPLE_NC = sys::DDCA_SIMPLE_NC as u16;
+        /// Like `SIMPLE_NC`, but also supports arbitrary values
+        const EXTENDED_NC = sys::DDCA_EXTENDED_NC as u16;
+        /// Non-continuous feature with a complex interpretation using one or more of SL, SH, ML, MH
+        const COMPLEX_NC = sys::DDCA_COMPLEX_NC as u16;
+        /// Non-continuous feature combining reserved values with a continuous range
+        const NC_CONT = sys::DDCA_NC_CONT as u16;
+        /// Write-only non-continuous feature
+        const WO_NC = sys::DDCA_WO_NC as u16;
+        /// Normal table feature
+        const NORMAL_TABLE = sys::DDCA_NORMAL_TABLE as u16;
+        /// Write-only table feature
+        const WO_TABLE = sys::DDCA_WO_TABLE as u16;
+
+        /// Continuous feature of any subtype
+        const CONT = Self::STD_CONT.bits() | Self::COMPLEX_CONT.bits();
+        /// Non-continuous feature of any subtype
+        const NC = Self::SIMPLE_NC.bits()
+            | Self::EXTENDED_NC.bits()
+            | Self::COMPLEX_NC.bits()
+            | Self::NC_CONT.bits()
+            | Self::WO_NC.bits();
+        /// Table feature of any subtype
+        const TABLE = Self::NORMAL_TABLE.bits() | Self::WO_TABLE.bits();
+
+        /// Feature is deprecated in the MCCS version
+        const DEPRECATED = sys::DDCA_DEPRECATED as u16;
+        /// Feature definition comes from a user-defined features file
+        const USER_DEFINED = sys::DDCA_USER_DEFINED as u16;
+        /// Feature definition was synthesized by the library (e.g. for unknown features)
+        const SYNTHETIC = sys::DDCA_SYNTHETIC as u16;
+    }
+}
+
+impl FeatureFlags {
+    pub fn is_readable(&self) -> bool {
+        self.contains(Self::RO)
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.contains(Self::WO)
+    }
+
+    pub fn is_continuous(&self) -> bool {
+        self.intersects(Self::CONT)
+    }
+
+    pub fn is_non_continuous(&self) -> bool {
+        self.intersects(Self::NC)
+    }
+
+    pub fn is_table(&self) -> bool {
+        self.intersects(Self::TABLE)
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.contains(Self::DEPRECATED)
+    }
+}
+
 pub struct FeatureSet(pub(crate) sys::DDCA_Feature_List);
 
 impl FeatureSet {
@@ -99,8 +180,8 @@ impl<'a> FeatureMetadata {
         unsafe { *self.0 }.vcp_version
     }
 
-    pub fn flags(&self) -> u16 {
-        unsafe { *self.0 }.feature_flags
+    pub fn flags(&self) -> FeatureFlags {
+        FeatureFlags::from_bits_retain(unsafe { *self.0 }.feature_flags)
     }
 
     pub fn sl_values(&'a self) -> &'a [FeatureValue] {
@@ -123,6 +204,34 @@ impl<'a> FeatureMetadata {
         unsafe { slice::from_raw_parts(sl_vals as *const FeatureValue, num) }
     }
 
+    /// Look up the name of an SL value in the feature's value table.
+    pub fn value_name(&'a self, sl: u8) -> Option<&'a str> {
+        self.sl_values()
+            .iter()
+            .find(|v| v.code() == sl)
+            .map(|v| v.name())
+    }
+
+    /// List the values of this feature with their names, if known.
+    ///
+    /// If the capabilities declare a list of values for this feature, that list is used (it
+    /// reflects what the display actually supports, and may include values missing from the
+    /// feature's value table). Otherwise all values from the feature's value table are listed.
+    pub fn supported_values(&'a self, caps: &DisplayCapabilities) -> Vec<(u8, Option<&'a str>)> {
+        match caps.vcp_code(self.feature_code()) {
+            Some(cap) if !cap.values().is_empty() => cap
+                .values()
+                .iter()
+                .map(|&v| (v, self.value_name(v)))
+                .collect(),
+            _ => self
+                .sl_values()
+                .iter()
+                .map(|v| (v.code(), Some(v.name())))
+                .collect(),
+        }
+    }
+
     pub fn name(&self) -> &CStr {
         unsafe { CStr::from_ptr((*self.0).feature_name) }
     }
@@ -132,6 +241,58 @@ impl<'a> FeatureMetadata {
     }
 }
 
+/// Owned copy of a feature value name, see [`FeatureValue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedFeatureValue {
+    pub code: u8,
+    pub name: String,
+}
+
+impl From<&FeatureValue> for OwnedFeatureValue {
+    fn from(val: &FeatureValue) -> Self {
+        OwnedFeatureValue {
+            code: val.code(),
+            name: val.name().to_owned(),
+        }
+    }
+}
+
+/// Owned copy of [`FeatureMetadata`], which does not borrow any memory from the library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedFeatureMetadata {
+    pub feature_code: u8,
+    pub vcp_version: MccsVersion,
+    pub name: String,
+    pub description: String,
+    pub flags: FeatureFlags,
+    pub sl_values: Vec<OwnedFeatureValue>,
+}
+
+impl OwnedFeatureMetadata {
+    /// Look up the name of an SL value in the feature's value table.
+    pub fn value_name(&self, sl: u8) -> Option<&str> {
+        self.sl_values
+            .iter()
+            .find(|v| v.code == sl)
+            .map(|v| v.name.as_str())
+    }
+}
+
+impl From<&FeatureMetadata> for OwnedFeatureMetadata {
+    fn from(meta: &FeatureMetadata) -> Self {
+        OwnedFeatureMetadata {
+            feature_code: meta.feature_code(),
+            vcp_version: meta.vcp_version(),
+            name: meta.name().to_string_lossy().into_owned(),
+            description: meta.description().to_string_lossy().into_owned(),
+            flags: meta.flags(),
+            sl_values: meta.sl_values().iter().map(Into::into).collect(),
+        }
+    }
+}
+
 impl Drop for FeatureMetadata {
     fn drop(&mut self) {
         unsafe {
@@ -139,3 +300,73 @@ impl Drop for FeatureMetadata {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::mem::ManuallyDrop;
+    use std::ptr;
+
+    #[test]
+    fn test_owned_metadata() {
+        let mut values = [
+            sys::DDCA_Feature_Value_Entry {
+                value_code: 0x01,
+                value_name: c"VGA-1".as_ptr().cast_mut(),
+            },
+            sys::DDCA_Feature_Value_Entry {
+                value_code: 0x0f,
+                value_name: c"DisplayPort-1".as_ptr().cast_mut(),
+            },
+            sys::DDCA_Feature_Value_Entry {
+                value_code: 0,
+                value_name: ptr::null_mut(),
+            },
+        ];
+        let mut raw = sys::DDCA_Feature_Metadata {
+            marker: [0; 4],
+            feature_code: 0x60,
+            vcp_version: MccsVersion { major: 2, minor: 2 },
+            feature_flags: (FeatureFlags::RW | FeatureFlags::SIMPLE_NC).bits(),
+            sl_values: values.as_mut_ptr(),
+            unused: ptr::null_mut(),
+            feature_name: c"Input Source".as_ptr().cast_mut(),
+            feature_desc: c"Selects active video source".as_ptr().cast_mut(),
+        };
+        // the metadata is not allocated by the library, so it must not be freed
+        let meta = ManuallyDrop::new(FeatureMetadata(&mut raw));
+
+        let owned = OwnedFeatureMetadata::from(&*meta);
+        assert_eq!(
+            owned,
+            OwnedFeatureMetadata {
+                feature_code: 0x60,
+                vcp_version: MccsVersion { major: 2, minor: 2 },
+                name: "Input Source".to_owned(),
+                description: "Selects active video source".to_owned(),
+                flags: FeatureFlags::RW | FeatureFlags::SIMPLE_NC,
+                sl_values: vec![
+                    OwnedFeatureValue {
+                        code: 0x01,
+                        name: "VGA-1".to_owned(),
+                    },
+                    OwnedFeatureValue {
+                        code: 0x0f,
+                        name: "DisplayPort-1".to_owned(),
+                    },
+                ],
+            }
+        );
+        assert_eq!(owned.value_name(0x0f), meta.value_name(0x0f));
+        assert_eq!(owned.value_name(0x11), None);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&owned).unwrap();
+            assert_eq!(
+                serde_json::from_str::<OwnedFeatureMetadata>(&json).unwrap(),
+                owned
+            );
+        }
+    }
+}
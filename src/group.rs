@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::VcpBackend;
+use crate::display::Display;
+use crate::display_info::{DisplayInfo, DisplayInfoList};
+use crate::err::{DdcError, Result};
+use crate::transition::{CancelToken, Easing, transition};
+use crate::{VcpValue, sys};
+
+/// VCP feature code for brightness.
+const BRIGHTNESS: u8 = 0x10;
+
+/// Per-display adjustment applied when mapping a logical percentage onto a display.
+///
+/// The logical value `p` (0-100) is mapped to `scale * 100 * (p / 100)^gamma + offset`, then
+/// clamped to 0-100 and scaled to the display's own maximum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjustment {
+    /// Offset in percentage points, e.g. `-10.0` for a display that is brighter than the others.
+    pub offset: f64,
+    /// Multiplier applied before the offset.
+    pub scale: f64,
+    /// Exponent of the response curve. `1.0` is linear.
+    pub gamma: f64,
+}
+
+impl Default for Adjustment {
+    fn default() -> Self {
+        Adjustment {
+            offset: 0.0,
+            scale: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl Adjustment {
+    /// Map a logical percentage to the percentage for this display.
+    pub fn apply(&self, percent: f64) -> f64 {
+        let p = (percent / 100.0).clamp(0.0, 1.0);
+        (self.scale * 100.0 * p.powf(self.gamma) + self.offset).clamp(0.0, 100.0)
+    }
+}
+
+/// A display in a [`DisplayGroup`].
+#[derive(Debug)]
+pub struct GroupMember<B = Display> {
+    pub display: B,
+    pub adjustment: Adjustment,
+    /// Maximum values of continuous features, read once per feature.
+    maxima: HashMap<u8, u16>,
+}
+
+impl<B: VcpBackend> GroupMember<B> {
+    pub fn new(display: B, adjustment: Adjustment) -> Self {
+        GroupMember {
+            display,
+            adjustment,
+            maxima: HashMap::new(),
+        }
+    }
+
+    /// Get the maximum value of a continuous feature.
+    pub fn max_value(&mut self, code: u8) -> Result<u16> {
+        if let Some(&max) = self.maxima.get(&code) {
+            return Ok(max);
+        }
+
+        match self.display.get_vcp_value(code)? {
+            VcpValue::Continuous { max, .. } => {
+                self.maxima.insert(code, max);
+                Ok(max)
+            }
+            _ => Err(DdcError::from_kind_rc(sys::DDCRC_INVALID_OPERATION)),
+        }
+    }
+
+    /// Raw value of a continuous feature for a logical percentage, after applying the adjustment.
+    pub fn raw_value(&mut self, code: u8, percent: f64) -> Result<u16> {
+        let max = self.max_value(code)?;
+        Ok((self.adjustment.apply(percent) / 100.0 * max as f64).round() as u16)
+    }
+
+    /// Set a continuous feature to a logical percentage, after applying the adjustment.
+    ///
+    /// Returns the raw value that was written.
+    pub fn set_percent(&mut self, code: u8, percent: f64) -> Result<u16> {
+        let value = self.raw_value(code, percent)?;
+        self.display.set_vcp_value(code, value)?;
+        Ok(value)
+    }
+
+    /// Like [`set_percent`](Self::set_percent), but with a [`transition`].
+    pub fn transition_percent(
+        &mut self,
+        code: u8,
+        percent: f64,
+        duration: Duration,
+        easing: Easing,
+        cancel: &CancelToken,
+    ) -> Result<u16> {
+        let value = self.raw_value(code, percent)?;
+        transition(&self.display, code, value, duration, easing, cancel)
+    }
+}
+
+/// A group of displays that are controlled together, e.g. to keep brightness in sync.
+///
+/// Operations on the group run on all displays in parallel, and return one result per display
+/// in the same order as [`DisplayGroup::members`].
+///
+/// The members are usually [`Display`]s, but can be any [`VcpBackend`], e.g. to replay a
+/// [trace](crate::trace).
+#[derive(Debug)]
+pub struct DisplayGroup<B = Display> {
+    members: Vec<GroupMember<B>>,
+}
+
+impl<B> Default for DisplayGroup<B> {
+    fn default() -> Self {
+        DisplayGroup {
+            members: Vec::new(),
+        }
+    }
+}
+
+impl DisplayGroup {
+    /// Open all displays in the list.
+    pub fn from_info_list(list: &DisplayInfoList) -> Result<Self> {
+        Self::from_filter(list, |_| true)
+    }
+
+    /// Open the displays in the list that match the predicate.
+    pub fn from_filter(
+        list: &DisplayInfoList,
+        mut predicate: impl FnMut(&DisplayInfo) -> bool,
+    ) -> Result<Self> {
+        let mut group = Self::new();
+        for info in list.into_iter().filter(|&i| predicate(i)) {
+            group.push(Display::from_display_info(info)?, Adjustment::default());
+        }
+
+        Ok(group)
+    }
+}
+
+impl<B: VcpBackend + Send> DisplayGroup<B> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, display: B, adjustment: Adjustment) {
+        self.members.push(GroupMember::new(display, adjustment));
+    }
+
+    pub fn members(&self) -> &[GroupMember<B>] {
+        &self.members
+    }
+
+    pub fn members_mut(&mut self) -> &mut [GroupMember<B>] {
+        &mut self.members
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Run `op` on every member in parallel, returning the results in member order.
+    pub fn for_each<T, F>(&mut self, op: F) -> Vec<Result<T>>
+    where
+        T: Send,
+        F: Fn(&mut GroupMember<B>) -> Result<T> + Sync,
+    {
+        let op = &op;
+        thread::scope(|s| {
+            let handles: Vec<_> = self
+                .members
+                .iter_mut()
+                .map(|m| s.spawn(move || op(m)))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        })
+    }
+
+    /// Set a continuous feature on all displays to a logical percentage (0-100), mapped onto
+    /// each display's own range with its adjustment.
+    ///
+    /// Returns the raw value written to each display.
+    pub fn set_percent(&mut self, code: u8, percent: f64) -> Vec<Result<u16>> {
+        self.for_each(|m| m.set_percent(code, percent))
+    }
+
+    /// Transition a continuous feature on all displays to a logical percentage, in parallel.
+    ///
+    /// Returns the last raw value written to each display.
+    pub fn transition_percent(
+        &mut self,
+        code: u8,
+        percent: f64,
+        duration: Duration,
+        easing: Easing,
+        cancel: &CancelToken,
+    ) -> Vec<Result<u16>> {
+        self.for_each(|m| m.transition_percent(code, percent, duration, easing, cancel))
+    }
+
+    /// Set the brightness of all displays to a logical percentage (0-100).
+    pub fn set_brightness(&mut self, percent: f64) -> Vec<Result<u16>> {
+        self.set_percent(BRIGHTNESS, percent)
+    }
+
+    /// Set the same raw value for a feature on all displays.
+    pub fn set_vcp_value(&mut self, code: u8, value: u16) -> Vec<Result<()>> {
+        self.for_each(|m| m.display.set_vcp_value(code, value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_adjustment() {
+        let linear = Adjustment::default();
+        assert_eq!(linear.apply(50.0), 50.0);
+        assert_eq!(linear.apply(150.0), 100.0);
+
+        let offset = Adjustment {
+            offset: -10.0,
+            ..Default::default()
+        };
+        assert_eq!(offset.apply(50.0), 40.0);
+        assert_eq!(offset.apply(5.0), 0.0);
+
+        let curve = Adjustment {
+            gamma: 2.0,
+            ..Default::default()
+        };
+        assert_eq!(curve.apply(50.0), 25.0);
+        assert_eq!(curve.apply(100.0), 100.0);
+    }
+}
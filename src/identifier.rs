@@ -0,0 +1,384 @@
+use std::ffi::CString;
+use std::str::FromStr;
+
+use crate::display::{DisplayIdentifier, SysDisplayIdentifier};
+use crate::display_info::{DisplayInfo, DisplayPath};
+use crate::err::{DdcError, Result};
+use crate::selector::{tokenize, write_value};
+use crate::sys;
+
+/// Error from parsing an [`OwnedDisplayIdentifier`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIdentifierError(String);
+
+impl std::fmt::Display for ParseIdentifierError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid display identifier: {0}", self.0)
+    }
+}
+
+impl std::error::Error for ParseIdentifierError {}
+
+/// Owned version of [`DisplayIdentifier`], e.g. for storing in configuration files.
+///
+/// [`FromStr`], [`to_selector_string`](Self::to_selector_string) and serde use the same
+/// `key=value` syntax as [`crate::DisplaySelector`]: `dispno=2`, `bus=6`,
+/// `mfg=DEL model="U2720Q" sn=ABC123`, `usb=1:4` or `hiddev=3`. [`std::fmt::Display`] uses the
+/// format of `ddca_did_repr` instead, see [`did_repr`](Self::did_repr).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OwnedDisplayIdentifier {
+    DisplayNumber(i32),
+    I2cBus(i32),
+    /// At least one of manufacturer, model, serial must be non-none
+    SerialNumber {
+        manufacturer: Option<String>,
+        model: Option<String>,
+        serial: Option<String>,
+    },
+    UsbDevice {
+        bus: i32,
+        device: i32,
+    },
+    UsbHid(i32),
+}
+
+impl OwnedDisplayIdentifier {
+    /// Call `f` with the borrowed form of this identifier.
+    ///
+    /// Fails with [`crate::DdcErrorKind::InvalidArgument`] if a string contains a NUL byte.
+    pub fn with_borrowed<R>(&self, f: impl FnOnce(DisplayIdentifier) -> R) -> Result<R> {
+        fn c_string(s: &Option<String>) -> Result<Option<CString>> {
+            s.as_deref()
+                .map(CString::new)
+                .transpose()
+                .map_err(|_| DdcError::from_kind_rc(sys::DDCRC_ARG))
+        }
+
+        Ok(match self {
+            Self::DisplayNumber(n) => f(DisplayIdentifier::DisplayNumber(*n)),
+            Self::I2cBus(bus) => f(DisplayIdentifier::I2cBus(*bus)),
+            Self::SerialNumber {
+                manufacturer,
+                model,
+                serial,
+            } => {
+                let (manufacturer, model, serial) =
+                    (c_string(manufacturer)?, c_string(model)?, c_string(serial)?);
+                f(DisplayIdentifier::SerialNumber {
+                    manufacturer: manufacturer.as_deref(),
+                    model: model.as_deref(),
+                    serial: serial.as_deref(),
+                })
+            }
+            Self::UsbDevice { bus, device } => f(DisplayIdentifier::UsbDevice {
+                bus: *bus,
+                device: *device,
+            }),
+            Self::UsbHid(dev) => f(DisplayIdentifier::UsbHid(*dev)),
+        })
+    }
+
+    /// Whether this identifier refers to the display described by `info`.
+    pub fn matches(&self, info: &DisplayInfo) -> bool {
+        let eq = |want: &Option<String>, have: &str| want.as_deref().is_none_or(|w| w == have);
+
+        match self {
+            Self::DisplayNumber(n) => info.display_no() == *n,
+            Self::I2cBus(bus) => info.path() == DisplayPath::I2C { bus: *bus },
+            Self::SerialNumber {
+                manufacturer,
+                model,
+                serial,
+            } => {
+                eq(manufacturer, info.manufacturer())
+                    && eq(model, info.model())
+                    && eq(serial, info.serial_number())
+            }
+            Self::UsbDevice { bus, device } => info
+                .usb_location()
+                .is_some_and(|usb| usb.bus == *bus && usb.device == *device),
+            Self::UsbHid(dev) => info.path() == DisplayPath::USB { hiddev_devno: *dev },
+        }
+    }
+
+    /// The library's description of this identifier, from `ddca_did_repr`.
+    ///
+    /// This is the same as the [`std::fmt::Display`] form, but needs the library.
+    pub fn did_repr(&self) -> Result<String> {
+        let did = self.with_borrowed(SysDisplayIdentifier::new)??;
+        Ok(did.to_string())
+    }
+
+    /// Format the identifier in the `key=value` syntax it is parsed from.
+    pub fn to_selector_string(&self) -> String {
+        Selector(self).to_string()
+    }
+}
+
+impl From<DisplayIdentifier<'_>> for OwnedDisplayIdentifier {
+    fn from(id: DisplayIdentifier<'_>) -> Self {
+        let owned = |s: Option<&std::ffi::CStr>| s.map(|s| s.to_string_lossy().into_owned());
+
+        match id {
+            DisplayIdentifier::DisplayNumber(n) => Self::DisplayNumber(n),
+            DisplayIdentifier::I2cBus(bus) => Self::I2cBus(bus),
+            DisplayIdentifier::SerialNumber {
+                manufacturer,
+                model,
+                serial,
+            } => Self::SerialNumber {
+                manufacturer: owned(manufacturer),
+                model: owned(model),
+                serial: owned(serial),
+            },
+            DisplayIdentifier::UsbDevice { bus, device } => Self::UsbDevice { bus, device },
+            DisplayIdentifier::UsbHid(dev) => Self::UsbHid(dev),
+        }
+    }
+}
+
+impl FromStr for OwnedDisplayIdentifier {
+    type Err = ParseIdentifierError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let err = |msg: String| ParseIdentifierError(msg);
+        let num = |key: &str, v: &str| {
+            v.parse::<i32>()
+                .map_err(|_| err(format!("invalid number for `{key}`: {v:?}")))
+        };
+
+        let terms = tokenize(s).map_err(|e| err(e.to_string()))?;
+        let (mut manufacturer, mut model, mut serial) = (None, None, None);
+        let mut other = None;
+
+        for (key, value) in terms {
+            if value.contains('\0') {
+                return Err(err(format!("value for `{key}` contains a NUL byte")));
+            }
+
+            let id = match key.as_str() {
+                "mfg" => {
+                    manufacturer = Some(value);
+                    continue;
+                }
+                "model" => {
+                    model = Some(value);
+                    continue;
+                }
+                "sn" => {
+                    serial = Some(value);
+                    continue;
+                }
+                "dispno" => Self::DisplayNumber(num(&key, &value)?),
+                "bus" => Self::I2cBus(num(&key, &value)?),
+                "hiddev" => Self::UsbHid(num(&key, &value)?),
+                "usb" => {
+                    let (bus, device) = value
+                        .split_once(':')
+                        .ok_or_else(|| err(format!("expected `usb=bus:device`, got {value:?}")))?;
+                    Self::UsbDevice {
+                        bus: num(&key, bus)?,
+                        device: num(&key, device)?,
+                    }
+                }
+                _ => return Err(err(format!("unknown key `{key}`"))),
+            };
+
+            if other.replace(id).is_some() {
+                return Err(err("more than one kind of identifier".to_owned()));
+            }
+        }
+
+        let has_sn = manufacturer.is_some() || model.is_some() || serial.is_some();
+        match (other, has_sn) {
+            (Some(id), false) => Ok(id),
+            (None, true) => Ok(Self::SerialNumber {
+                manufacturer,
+                model,
+                serial,
+            }),
+            (Some(_), true) => Err(err("more than one kind of identifier".to_owned())),
+            (None, false) => Err(err("empty identifier".to_owned())),
+        }
+    }
+}
+
+/// Formats an identifier in the `key=value` syntax.
+struct Selector<'a>(&'a OwnedDisplayIdentifier);
+
+impl std::fmt::Display for Selector<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            OwnedDisplayIdentifier::DisplayNumber(n) => write!(f, "dispno={n}"),
+            OwnedDisplayIdentifier::I2cBus(bus) => write!(f, "bus={bus}"),
+            OwnedDisplayIdentifier::SerialNumber {
+                manufacturer,
+                model,
+                serial,
+            } => {
+                let mut sep = "";
+                for (key, val) in [("mfg", manufacturer), ("model", model), ("sn", serial)] {
+                    if let Some(val) = val {
+                        write!(f, "{sep}{key}=")?;
+                        write_value(f, val)?;
+                        sep = " ";
+                    }
+                }
+                Ok(())
+            }
+            OwnedDisplayIdentifier::UsbDevice { bus, device } => write!(f, "usb={bus}:{device}"),
+            OwnedDisplayIdentifier::UsbHid(dev) => write!(f, "hiddev={dev}"),
+        }
+    }
+}
+
+impl std::fmt::Display for OwnedDisplayIdentifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let opt = |s: &Option<String>| s.clone().unwrap_or_default();
+
+        write!(f, "Display Id[type=")?;
+        match self {
+            Self::DisplayNumber(n) => write!(f, "DISP_ID_DISPNO, dispno={n}")?,
+            Self::I2cBus(bus) => write!(f, "DISP_ID_BUSNO, bus=/dev/i2c-{bus}")?,
+            Self::SerialNumber {
+                manufacturer,
+                model,
+                serial,
+            } => write!(
+                f,
+                "DISP_ID_MONSER, mfg={0}, model={1}, sn={2}",
+                opt(manufacturer),
+                opt(model),
+                opt(serial),
+            )?,
+            Self::UsbDevice { bus, device } => {
+                write!(f, "DISP_ID_USB, usb bus:device={bus}.{device}")?
+            }
+            Self::UsbHid(dev) => write!(f, "DISP_ID_HIDDEV, hiddev_devno={dev}")?,
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for OwnedDisplayIdentifier {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(&Selector(self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OwnedDisplayIdentifier {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        for s in [
+            "dispno=2",
+            "bus=6",
+            "mfg=DEL model=\"Dell U2720Q\" sn=ABC123",
+            "model=U2720Q",
+            "usb=1:4",
+            "hiddev=3",
+        ] {
+            let id: OwnedDisplayIdentifier = s.parse().unwrap();
+            assert_eq!(id.to_selector_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        for (s, repr) in [
+            ("dispno=2", "Display Id[type=DISP_ID_DISPNO, dispno=2]"),
+            ("bus=6", "Display Id[type=DISP_ID_BUSNO, bus=/dev/i2c-6]"),
+            (
+                "mfg=DEL sn=ABC123",
+                "Display Id[type=DISP_ID_MONSER, mfg=DEL, model=, sn=ABC123]",
+            ),
+            (
+                "usb=1:4",
+                "Display Id[type=DISP_ID_USB, usb bus:device=1.4]",
+            ),
+            (
+                "hiddev=3",
+                "Display Id[type=DISP_ID_HIDDEV, hiddev_devno=3]",
+            ),
+        ] {
+            let id: OwnedDisplayIdentifier = s.parse().unwrap();
+            assert_eq!(id.to_string(), repr);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let ids = [
+            OwnedDisplayIdentifier::DisplayNumber(2),
+            OwnedDisplayIdentifier::I2cBus(6),
+            OwnedDisplayIdentifier::SerialNumber {
+                manufacturer: Some("DEL".to_owned()),
+                model: Some("Dell \"U2720Q\" 27\\".to_owned()),
+                serial: None,
+            },
+            OwnedDisplayIdentifier::SerialNumber {
+                manufacturer: None,
+                model: None,
+                serial: Some(String::new()),
+            },
+            OwnedDisplayIdentifier::UsbDevice { bus: 1, device: 4 },
+            OwnedDisplayIdentifier::UsbHid(3),
+        ];
+
+        for id in ids {
+            let json = serde_json::to_string(&id).unwrap();
+            assert_eq!(
+                serde_json::from_str::<OwnedDisplayIdentifier>(&json).unwrap(),
+                id,
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for s in [
+            "",
+            "bus=6 dispno=2",
+            "bus=6 sn=X",
+            "usb=4",
+            "sn=\"a\0b\"",
+            "x=1",
+        ] {
+            assert!(
+                s.parse::<OwnedDisplayIdentifier>().is_err(),
+                "{s:?} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_borrowed() {
+        let id = DisplayIdentifier::SerialNumber {
+            manufacturer: Some(c"DEL"),
+            model: None,
+            serial: Some(c"ABC123"),
+        };
+        assert_eq!(
+            OwnedDisplayIdentifier::from(id),
+            "mfg=DEL sn=ABC123".parse().unwrap()
+        );
+    }
+}
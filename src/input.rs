@@ -0,0 +1,301 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{DdcError, Display, Result, VcpValue, sys};
+
+/// VCP feature code for the input source.
+const INPUT_SOURCE: u8 = 0x60;
+
+/// An input of a display, as listed by [`Display::input_sources`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSource {
+    /// The value of feature 0x60 that selects this input.
+    pub code: u8,
+    /// Name from the MCCS value table, e.g. "DisplayPort-1". Many monitors use codes that are not
+    /// in the table, which have no name.
+    pub name: Option<String>,
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn parse_code(s: &str) -> Option<u8> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+impl InputSource {
+    /// Whether `name` refers to this input: its name, ignoring case, spaces and punctuation (so
+    /// "hdmi 1" matches "HDMI-1"), or its code in hex ("0x11") or decimal.
+    pub fn matches(&self, name: &str) -> bool {
+        if parse_code(name) == Some(self.code) {
+            return true;
+        }
+        let name = normalize(name);
+        !name.is_empty() && self.name.as_deref().map(normalize) == Some(name)
+    }
+}
+
+impl std::fmt::Display for InputSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "0x{0:02x}", self.code),
+        }
+    }
+}
+
+/// Options for [`Display::switch_input`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchOptions {
+    /// Wait until the display reports the new input before returning.
+    pub verify: bool,
+    /// How long to wait for the display to report the new input.
+    pub timeout: Duration,
+    /// Time between reads while waiting. Most monitors stop responding for a second or two while
+    /// they switch, so failed reads are retried until the timeout.
+    pub interval: Duration,
+}
+
+impl Default for SwitchOptions {
+    fn default() -> Self {
+        SwitchOptions {
+            verify: false,
+            timeout: Duration::from_secs(5),
+            interval: Duration::from_millis(250),
+        }
+    }
+}
+
+impl SwitchOptions {
+    /// Wait up to `timeout` for the display to report the new input.
+    pub fn verified(timeout: Duration) -> Self {
+        SwitchOptions {
+            verify: true,
+            timeout,
+            ..Default::default()
+        }
+    }
+}
+
+impl Display {
+    /// List the inputs of the display.
+    ///
+    /// The values declared for feature 0x60 in the capabilities are used if there are any,
+    /// otherwise all inputs from the MCCS value table. See
+    /// [`FeatureMetadata::supported_values`](crate::FeatureMetadata::supported_values).
+    pub fn input_sources(&self) -> Result<Vec<InputSource>> {
+        let caps = self.get_capabilities()?;
+        let meta = self.get_feature_metadata(INPUT_SOURCE)?;
+
+        Ok(meta
+            .supported_values(&caps)
+            .into_iter()
+            .map(|(code, name)| InputSource {
+                code,
+                name: name.map(str::to_owned),
+            })
+            .collect())
+    }
+
+    /// Get the code of the current input.
+    ///
+    /// Only the SL byte is returned: some monitors put unrelated data in the SH byte.
+    pub fn input_source(&self) -> Result<u8> {
+        match self.get_vcp_value(INPUT_SOURCE)? {
+            VcpValue::NonContinuous { sl, .. } => Ok(sl),
+            // a user-defined feature file may declare the feature as continuous
+            VcpValue::Continuous { current, .. } => Ok(current as u8),
+            VcpValue::Table(_) => Err(DdcError::from_kind_rc(sys::DDCRC_BAD_DATA)),
+        }
+    }
+
+    /// Look up an input by name or code, see [`InputSource::matches`].
+    ///
+    /// A code that isn't among the [`input_sources`](Self::input_sources) is still accepted, since
+    /// some monitors don't list all of their inputs. Fails with `DDCRC_NOT_FOUND` otherwise.
+    pub fn find_input_source(&self, name: &str) -> Result<InputSource> {
+        if let Some(input) = self.input_sources()?.into_iter().find(|i| i.matches(name)) {
+            return Ok(input);
+        }
+
+        match parse_code(name) {
+            Some(code) => Ok(InputSource { code, name: None }),
+            None => Err(DdcError::from_kind_rc(sys::DDCRC_NOT_FOUND)),
+        }
+    }
+
+    /// Switch to an input.
+    ///
+    /// With [`SwitchOptions::verify`], the input is read back until it matches or the timeout
+    /// expires. On timeout, this fails with the last read error, or `DDCRC_VERIFY` if the display
+    /// responded with a different input.
+    pub fn switch_input(&self, code: u8, opts: &SwitchOptions) -> Result<()> {
+        self.set_vcp_value(INPUT_SOURCE, code.into())?;
+        if !opts.verify {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        loop {
+            thread::sleep(opts.interval);
+            let err = match self.input_source() {
+                Ok(current) if current == code => return Ok(()),
+                Ok(_) => DdcError::from_kind_rc(sys::DDCRC_VERIFY),
+                Err(e) => e,
+            };
+            if start.elapsed() >= opts.timeout {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Switch to an input by name or code, see [`find_input_source`](Self::find_input_source).
+    pub fn switch_input_by_name(&self, name: &str, opts: &SwitchOptions) -> Result<InputSource> {
+        let input = self.find_input_source(name)?;
+        self.switch_input(input.code, opts)?;
+        Ok(input)
+    }
+}
+
+/// One of the two machines a [`Kvm`] switches between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KvmSide {
+    A,
+    B,
+}
+
+impl KvmSide {
+    pub fn other(self) -> Self {
+        match self {
+            KvmSide::A => KvmSide::B,
+            KvmSide::B => KvmSide::A,
+        }
+    }
+}
+
+/// A display with its inputs for both sides of a [`Kvm`].
+#[derive(Debug)]
+pub struct KvmPort {
+    pub display: Display,
+    pub a: u8,
+    pub b: u8,
+}
+
+impl KvmPort {
+    pub fn new(display: Display, a: u8, b: u8) -> Self {
+        KvmPort { display, a, b }
+    }
+
+    /// Look up both inputs by name or code, see [`Display::find_input_source`].
+    pub fn by_name(display: Display, a: &str, b: &str) -> Result<Self> {
+        let a = display.find_input_source(a)?.code;
+        let b = display.find_input_source(b)?.code;
+        Ok(Self::new(display, a, b))
+    }
+
+    pub fn input(&self, side: KvmSide) -> u8 {
+        match side {
+            KvmSide::A => self.a,
+            KvmSide::B => self.b,
+        }
+    }
+
+    /// The side the display currently shows, or `None` if it is on another input.
+    pub fn side(&self) -> Result<Option<KvmSide>> {
+        let current = self.display.input_source()?;
+        Ok([KvmSide::A, KvmSide::B]
+            .into_iter()
+            .find(|&side| self.input(side) == current))
+    }
+}
+
+/// Switches a set of displays between two machines together, like a KVM switch without the
+/// keyboard and mouse.
+///
+/// By convention side A is the machine running this code: a display showing the other machine
+/// may not respond on this machine's DDC bus.
+#[derive(Debug, Default)]
+pub struct Kvm {
+    ports: Vec<KvmPort>,
+    pub options: SwitchOptions,
+}
+
+impl Kvm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, port: KvmPort) {
+        self.ports.push(port);
+    }
+
+    pub fn ports(&self) -> &[KvmPort] {
+        &self.ports
+    }
+
+    /// The side shown by the first display that reports one.
+    pub fn side(&self) -> Option<KvmSide> {
+        self.ports.iter().find_map(|p| p.side().ok().flatten())
+    }
+
+    /// Switch all displays to a side in parallel, returning the results in port order.
+    pub fn switch_to(&mut self, side: KvmSide) -> Vec<Result<()>> {
+        let opts = &self.options;
+        thread::scope(|s| {
+            let handles: Vec<_> = self
+                .ports
+                .iter_mut()
+                .map(|p| s.spawn(move || p.display.switch_input(p.input(side), opts)))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        })
+    }
+
+    /// Switch all displays to the other side from the one they show. If no display reports a
+    /// side, they are switched to side A.
+    ///
+    /// Returns the side switched to, and the result for each port.
+    pub fn toggle(&mut self) -> (KvmSide, Vec<Result<()>>) {
+        let target = self.side().map_or(KvmSide::A, KvmSide::other);
+        (target, self.switch_to(target))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn input_names() {
+        let dp = InputSource {
+            code: 0x0f,
+            name: Some("DisplayPort-1".to_owned()),
+        };
+        assert!(dp.matches("displayport 1"));
+        assert!(dp.matches("0x0F"));
+        assert!(dp.matches("15"));
+        assert!(!dp.matches("DisplayPort-2"));
+        assert!(!dp.matches(""));
+        assert_eq!(dp.to_string(), "DisplayPort-1");
+
+        let vendor = InputSource {
+            code: 0xd0,
+            name: None,
+        };
+        assert!(vendor.matches("0xd0"));
+        assert!(!vendor.matches("d0"));
+        assert_eq!(vendor.to_string(), "0xd0");
+    }
+}
@@ -4,20 +4,69 @@
 //!  - Version 2.1.x of `ddcutil` must be installed
 //!  - Building requires `pkg-config` to locate the `libddcutil` headers
 //!  - `ddcutil` is linux-only
+#[cfg(feature = "ambient")]
+pub mod ambient;
+mod backend;
+#[cfg(feature = "cache")]
+pub mod cache;
 mod capabilities;
+mod color;
+#[cfg(feature = "dbus")]
+pub mod dbus;
 mod display;
 mod display_info;
 mod err;
+pub mod events;
+#[cfg(feature = "exporter")]
+pub mod exporter;
 mod feature_metadata;
+mod group;
+mod identifier;
+mod input;
+mod lib_info;
+mod locks;
 mod macros;
+mod output;
+mod power;
+mod retry;
+#[cfg(feature = "rsd")]
+pub mod rsd;
+#[cfg(feature = "schedule")]
+pub mod schedule;
+mod selector;
+#[cfg(feature = "trace")]
+pub mod trace;
+mod transition;
+pub mod udf;
 
 pub mod sys;
 
 // re-exports of wrapper types & functions from other submodules
-pub use display::{Display, DisplayIdentifier, TableValue};
-pub use display_info::{DisplayInfo, DisplayInfoList, DisplayPath, get_display_info_list};
-pub use err::{DdcError, Result};
-pub use feature_metadata::{FeatureMetadata, FeatureValue};
+pub use backend::VcpBackend;
+pub use capabilities::{CapVcp, DisplayCapabilities};
+pub use color::{ColorAdjustments, Gamma, Level, Rgb, SixAxis};
+pub use display::{Display, DisplayIdentifier, DisplayRef, TableValue, VcpValue, WriteOptions};
+pub use display_info::{
+    DisplayInfo, DisplayInfoList, DisplayPath, OwnedDisplayInfo, UsbLocation, get_display_info_list,
+};
+pub use err::{DdcError, DdcErrorKind, Result};
+pub use feature_metadata::{
+    FeatureFlags, FeatureMetadata, FeatureSet, FeatureValue, OwnedFeatureMetadata,
+    OwnedFeatureValue,
+};
+pub use group::{Adjustment, DisplayGroup, GroupMember};
+pub use identifier::{OwnedDisplayIdentifier, ParseIdentifierError};
+pub use input::{InputSource, Kvm, KvmPort, KvmSide, SwitchOptions};
+pub use lib_info::{BuildOptions, LibraryInfo, lib_build_options};
+pub use locks::{LockRecord, LockReport, Watchdog};
+pub use output::{
+    OutputLevel, OutputLevelGuard, ParseLevelError, SyslogLevel, lib_output_level,
+    lib_raise_output_level, lib_set_output_level,
+};
+pub use power::{PowerMode, WakeOptions, wake_all};
+pub use retry::RetryPolicy;
+pub use selector::{DisplaySelector, SelectorError};
+pub use transition::{CancelToken, Easing, transition};
 
 #[cfg(feature = "anyhow")]
 pub use err::ConvertToAnyhow;
@@ -27,7 +76,37 @@ pub type MccsVersion = sys::DDCA_MCCS_Version_Spec;
 pub type DdcutilVersion = sys::DDCA_Ddcutil_Version_Spec;
 
 // Imports
-use std::{ffi::CStr, ptr};
+use std::{
+    ffi::{CStr, CString, c_char},
+    ptr,
+};
+
+/// Encode bytes as a lower-case hex string.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut s, b| {
+            let _ = write!(s, "{b:02x}");
+            s
+        })
+}
+
+/// Copy a string allocated by libddcutil into a `String`, and free the original.
+///
+/// Safety: `s` must be null or a valid C string that the caller is responsible for freeing.
+pub(crate) unsafe fn take_c_string(s: *mut c_char) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+
+    unsafe {
+        let ret = CStr::from_ptr(s).to_string_lossy().into_owned();
+        libc::free(s as *mut libc::c_void);
+        ret
+    }
+}
 
 impl std::fmt::Display for MccsVersion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -35,6 +114,53 @@ impl std::fmt::Display for MccsVersion {
     }
 }
 
+impl PartialEq for MccsVersion {
+    fn eq(&self, other: &Self) -> bool {
+        (self.major, self.minor) == (other.major, other.minor)
+    }
+}
+
+impl Eq for MccsVersion {}
+
+impl std::hash::Hash for MccsVersion {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (self.major, self.minor).hash(state);
+    }
+}
+
+/// Parses versions in the "major.minor" form used by `Display`, e.g. "2.1"
+impl std::str::FromStr for MccsVersion {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (major, minor) = s.split_once('.').unwrap_or((s, "0"));
+        Ok(MccsVersion {
+            major: major.trim().parse()?,
+            minor: minor.trim().parse()?,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MccsVersion {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MccsVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 // misc utility functions
 
 pub fn lib_version() -> DdcutilVersion {
@@ -67,12 +193,41 @@ pub fn lib_filename() -> &'static str {
 
 pub type BuildOptionFlags = sys::DDCA_Build_Option_Flags;
 
-/// Returns a bitfield of `BuildOptionFlags`
+/// Returns a bitfield of `BuildOptionFlags`. See [`lib_build_options`] for a typed version.
 pub fn lib_build_flags() -> BuildOptionFlags {
     unsafe { sys::ddca_build_options() }
 }
 
-pub type SysLogLevel = sys::DDCA_Syslog_Level;
+/// Whether the library was built with support for displays controlled over USB HID.
+pub fn lib_has_usb_support() -> bool {
+    lib_build_options().contains(BuildOptions::USB)
+}
+
+/// Run `f` with the library's output redirected into the returned string.
+///
+/// Capturing is per-thread, so output from other threads is not included. Messages the library
+/// writes to stderr are captured too.
+pub fn capture_output(f: impl FnOnce()) -> String {
+    /// Ends the capture if `f` panics, so the thread's output isn't left redirected.
+    struct Capture;
+
+    impl Drop for Capture {
+        fn drop(&mut self) {
+            unsafe { drop(take_c_string(sys::ddca_end_capture())) };
+        }
+    }
+
+    unsafe {
+        sys::ddca_start_capture(sys::DDCA_Capture_Option_Flags::DDCA_CAPTURE_STDERR);
+    }
+    let capture = Capture;
+    f();
+    std::mem::forget(capture);
+    unsafe { take_c_string(sys::ddca_end_capture()) }
+}
+
+#[deprecated(note = "use `SyslogLevel`")]
+pub type SysLogLevel = SyslogLevel;
 pub type LibInitOpts = sys::DDCA_Init_Options;
 impl From<u32> for LibInitOpts {
     fn from(val: u32) -> Self {
@@ -80,13 +235,20 @@ impl From<u32> for LibInitOpts {
     }
 }
 
-pub fn lib_init(libopts: Option<&str>, log_level: SysLogLevel, opts: LibInitOpts) -> Result<()> {
+/// Initialize the library.
+///
+/// The output level can be set after initialization with [`lib_set_output_level`], e.g. from a
+/// `--verbose` flag with [`OutputLevel::from_verbosity`].
+pub fn lib_init(libopts: Option<&str>, log_level: SyslogLevel, opts: LibInitOpts) -> Result<()> {
+    let libopts = libopts
+        .map(CString::new)
+        .transpose()
+        .map_err(|_| DdcError::from_kind_rc(sys::DDCRC_ARG))?;
+
     unsafe {
         let rc = sys::ddca_init(
-            libopts
-                .map(|o| o.as_ptr() as *const i8)
-                .unwrap_or(ptr::null()),
-            log_level,
+            libopts.as_deref().map(CStr::as_ptr).unwrap_or(ptr::null()),
+            log_level.into(),
             opts,
         );
         DdcError::check(rc)?;
@@ -98,6 +260,9 @@ pub fn lib_init(libopts: Option<&str>, log_level: SysLogLevel, opts: LibInitOpts
 }
 
 /// Turn verification of setting VCP values on/off. Returns previous setting.
+///
+/// This applies to the whole process. See [`Display::set_vcp_value_with`] for verifying
+/// individual writes.
 pub fn lib_set_verify(onoff: bool) -> bool {
     unsafe { sys::ddca_enable_verify(onoff) }
 }
@@ -142,3 +307,14 @@ pub fn get_feature_metadata(code: u8, version: MccsVersion) -> Result<FeatureMet
 
     Ok(fm)
 }
+
+/// Build the metadata for all 256 feature codes for the given MCCS version.
+///
+/// Codes that the MCCS spec does not define get default metadata from the library, so the result
+/// always has one entry per code, in order. With the `serde` feature the result can be exported,
+/// e.g. as JSON, for use by tools that do not link libddcutil.
+pub fn feature_catalogue(version: MccsVersion) -> Result<Vec<OwnedFeatureMetadata>> {
+    (0..=u8::MAX)
+        .map(|code| Ok((&get_feature_metadata(code, version)?).into()))
+        .collect()
+}
@@ -0,0 +1,124 @@
+use crate::output::{OutputLevel, lib_output_level};
+use crate::sys;
+
+bitflags::bitflags! {
+    /// Optional features the library was built with.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct BuildOptions: u32 {
+        /// Support for displays controlled over USB HID
+        const USB = sys::DDCA_Build_Option_Flags::DDCA_BUILT_WITH_USB.0;
+        /// Error simulation for testing
+        const FAILSIM = sys::DDCA_Build_Option_Flags::DDCA_BUILT_WITH_FAILSIM.0;
+    }
+}
+
+/// Get the options the library was built with.
+pub fn lib_build_options() -> BuildOptions {
+    BuildOptions::from_bits_retain(crate::lib_build_flags().0)
+}
+
+/// Summary of the library version, build options and settings, e.g. for bug reports.
+///
+/// The `Display` implementation gives a human-readable multi-line report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LibraryInfo {
+    pub version: String,
+    pub extended_version: String,
+    pub filename: String,
+    pub build_options: BuildOptions,
+    pub verify_enabled: bool,
+    pub dynamic_sleep_enabled: bool,
+    pub udf_enabled: bool,
+    pub output_level: OutputLevel,
+}
+
+impl LibraryInfo {
+    /// Collect the current information from the library.
+    pub fn collect() -> Self {
+        LibraryInfo {
+            version: crate::lib_version_string().to_owned(),
+            extended_version: crate::lib_extended_version_string().to_owned(),
+            filename: crate::lib_filename().to_owned(),
+            build_options: lib_build_options(),
+            verify_enabled: crate::lib_is_verify_enabled(),
+            dynamic_sleep_enabled: crate::lib_is_dynamic_sleep_enabled(),
+            udf_enabled: crate::lib_is_udf_enabled(),
+            output_level: lib_output_level(),
+        }
+    }
+}
+
+impl std::fmt::Display for LibraryInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let onoff = |b: bool| if b { "on" } else { "off" };
+        let options: Vec<_> = self.build_options.iter_names().map(|(n, _)| n).collect();
+
+        writeln!(f, "libddcutil version: {0}", self.extended_version)?;
+        writeln!(f, "libddcutil file:    {0}", self.filename)?;
+        writeln!(
+            f,
+            "Build options:      {0}",
+            if options.is_empty() {
+                "none".to_owned()
+            } else {
+                options.join(", ")
+            }
+        )?;
+        writeln!(f, "Verify:             {0}", onoff(self.verify_enabled))?;
+        writeln!(
+            f,
+            "Dynamic sleep:      {0}",
+            onoff(self.dynamic_sleep_enabled)
+        )?;
+        writeln!(f, "User features:      {0}", onoff(self.udf_enabled))?;
+        writeln!(f, "Output level:       {0}", self.output_level)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_options() {
+        use sys::DDCA_Build_Option_Flags as F;
+        assert_eq!(BuildOptions::USB.bits(), F::DDCA_BUILT_WITH_USB.0);
+        assert_eq!(BuildOptions::FAILSIM.bits(), F::DDCA_BUILT_WITH_FAILSIM.0);
+
+        // bits the crate doesn't know about are kept
+        let opts = BuildOptions::from_bits_retain(BuildOptions::USB.bits() | 0x8000);
+        assert!(opts.contains(BuildOptions::USB));
+        assert_eq!(opts.bits() & 0x8000, 0x8000);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut info = LibraryInfo {
+            version: "2.2.0".to_owned(),
+            extended_version: "2.2.0-dev".to_owned(),
+            filename: "/usr/lib/libddcutil.so.5".to_owned(),
+            build_options: BuildOptions::USB | BuildOptions::FAILSIM,
+            verify_enabled: true,
+            dynamic_sleep_enabled: false,
+            udf_enabled: true,
+            output_level: OutputLevel::Verbose,
+        };
+        // the output level is named by the library, so it's left out here
+        let report = info.to_string();
+        assert!(report.starts_with(
+            "\
+libddcutil version: 2.2.0-dev
+libddcutil file:    /usr/lib/libddcutil.so.5
+Build options:      USB, FAILSIM
+Verify:             on
+Dynamic sleep:      off
+User features:      on
+Output level:       "
+        ));
+
+        info.build_options = BuildOptions::empty();
+        assert!(info.to_string().contains("Build options:      none\n"));
+    }
+}
@@ -0,0 +1,249 @@
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use crate::sys;
+
+/// Error from parsing an [`OutputLevel`] or [`SyslogLevel`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelError(String);
+
+impl std::fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown level: {0:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseLevelError {}
+
+/// Verbosity of the messages the library writes to its output stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum OutputLevel {
+    Terse,
+    Normal,
+    Verbose,
+    VeryVerbose,
+}
+
+impl OutputLevel {
+    /// Map a count of `--verbose` flags to an output level: none is `Normal`, one is `Verbose`,
+    /// more is `VeryVerbose`.
+    pub fn from_verbosity(count: u8) -> Self {
+        match count {
+            0 => OutputLevel::Normal,
+            1 => OutputLevel::Verbose,
+            _ => OutputLevel::VeryVerbose,
+        }
+    }
+}
+
+impl From<OutputLevel> for sys::DDCA_Output_Level {
+    fn from(val: OutputLevel) -> Self {
+        match val {
+            OutputLevel::Terse => sys::DDCA_Output_Level::DDCA_OL_TERSE,
+            OutputLevel::Normal => sys::DDCA_Output_Level::DDCA_OL_NORMAL,
+            OutputLevel::Verbose => sys::DDCA_Output_Level::DDCA_OL_VERBOSE,
+            OutputLevel::VeryVerbose => sys::DDCA_Output_Level::DDCA_OL_VV,
+        }
+    }
+}
+
+impl From<sys::DDCA_Output_Level> for OutputLevel {
+    fn from(val: sys::DDCA_Output_Level) -> Self {
+        // levels are ordered bit values, so round down to the closest known level
+        if val.0 >= sys::DDCA_Output_Level::DDCA_OL_VV.0 {
+            OutputLevel::VeryVerbose
+        } else if val.0 >= sys::DDCA_Output_Level::DDCA_OL_VERBOSE.0 {
+            OutputLevel::Verbose
+        } else if val.0 >= sys::DDCA_Output_Level::DDCA_OL_NORMAL.0 {
+            OutputLevel::Normal
+        } else {
+            OutputLevel::Terse
+        }
+    }
+}
+
+/// Uses the library's name for the level, from `ddca_output_level_name`.
+impl std::fmt::Display for OutputLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = unsafe { sys::ddca_output_level_name((*self).into()) };
+        if name.is_null() {
+            return write!(f, "{self:?}");
+        }
+
+        f.write_str(&unsafe { CStr::from_ptr(name) }.to_string_lossy())
+    }
+}
+
+/// Parses the names used by `Display`, case-insensitively. `vv` is accepted for `VeryVerbose`,
+/// like the `--vv` option of ddcutil.
+impl FromStr for OutputLevel {
+    type Err = ParseLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("vv") {
+            return Ok(OutputLevel::VeryVerbose);
+        }
+
+        [
+            OutputLevel::Terse,
+            OutputLevel::Normal,
+            OutputLevel::Verbose,
+            OutputLevel::VeryVerbose,
+        ]
+        .into_iter()
+        .find(|level| level.to_string().eq_ignore_ascii_case(s))
+        .ok_or_else(|| ParseLevelError(s.to_owned()))
+    }
+}
+
+/// Minimum severity of messages the library writes to the system log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum SyslogLevel {
+    /// Use the level from the configuration file, or the library default.
+    NotSet,
+    Never,
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Verbose,
+    Debug,
+}
+
+impl From<SyslogLevel> for sys::DDCA_Syslog_Level {
+    fn from(val: SyslogLevel) -> Self {
+        use sys::DDCA_Syslog_Level as L;
+        match val {
+            SyslogLevel::NotSet => L::DDCA_SYSLOG_NOT_SET,
+            SyslogLevel::Never => L::DDCA_SYSLOG_NEVER,
+            SyslogLevel::Error => L::DDCA_SYSLOG_ERROR,
+            SyslogLevel::Warning => L::DDCA_SYSLOG_WARNING,
+            SyslogLevel::Notice => L::DDCA_SYSLOG_NOTICE,
+            SyslogLevel::Info => L::DDCA_SYSLOG_INFO,
+            SyslogLevel::Verbose => L::DDCA_SYSLOG_VERBOSE,
+            SyslogLevel::Debug => L::DDCA_SYSLOG_DEBUG,
+        }
+    }
+}
+
+impl From<sys::DDCA_Syslog_Level> for SyslogLevel {
+    fn from(val: sys::DDCA_Syslog_Level) -> Self {
+        use sys::DDCA_Syslog_Level as L;
+        match val {
+            L::DDCA_SYSLOG_NOT_SET => SyslogLevel::NotSet,
+            L::DDCA_SYSLOG_NEVER => SyslogLevel::Never,
+            L::DDCA_SYSLOG_ERROR => SyslogLevel::Error,
+            L::DDCA_SYSLOG_WARNING => SyslogLevel::Warning,
+            L::DDCA_SYSLOG_NOTICE => SyslogLevel::Notice,
+            L::DDCA_SYSLOG_INFO => SyslogLevel::Info,
+            L::DDCA_SYSLOG_VERBOSE => SyslogLevel::Verbose,
+            L::DDCA_SYSLOG_DEBUG => SyslogLevel::Debug,
+        }
+    }
+}
+
+/// Uses the library's name for the level, from `ddca_syslog_level_name`.
+impl std::fmt::Display for SyslogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = unsafe { sys::ddca_syslog_level_name((*self).into()) };
+        if name.is_null() {
+            return write!(f, "{self:?}");
+        }
+
+        f.write_str(&unsafe { CStr::from_ptr(name) }.to_string_lossy())
+    }
+}
+
+/// Parses the level names the library accepts, with `ddca_syslog_level_from_name`.
+///
+/// The library doesn't have a name for [`SyslogLevel::NotSet`], so it can't be parsed.
+impl FromStr for SyslogLevel {
+    type Err = ParseLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseLevelError(s.to_owned());
+        let name = CString::new(s).map_err(|_| err())?;
+
+        match unsafe { sys::ddca_syslog_level_from_name(name.as_ptr()) }.into() {
+            SyslogLevel::NotSet => Err(err()),
+            level => Ok(level),
+        }
+    }
+}
+
+pub fn lib_output_level() -> OutputLevel {
+    unsafe { sys::ddca_get_output_level() }.into()
+}
+
+/// Set the output level. Returns the previous level.
+pub fn lib_set_output_level(level: OutputLevel) -> OutputLevel {
+    unsafe { sys::ddca_set_output_level(level.into()) }.into()
+}
+
+/// Restores the previous output level when dropped, see [`lib_raise_output_level`].
+#[derive(Debug)]
+#[must_use = "the output level is restored when the guard is dropped"]
+pub struct OutputLevelGuard {
+    previous: OutputLevel,
+    // the output level may be tracked per thread, so restore it on the same thread
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for OutputLevelGuard {
+    fn drop(&mut self) {
+        lib_set_output_level(self.previous);
+    }
+}
+
+/// Raise the output level to at least `level` until the returned guard is dropped, e.g. to get
+/// detailed output for a single operation. The level is never lowered.
+pub fn lib_raise_output_level(level: OutputLevel) -> OutputLevelGuard {
+    let previous = lib_output_level();
+    lib_set_output_level(previous.max(level));
+
+    OutputLevelGuard {
+        previous,
+        _not_send: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_level_conversions() {
+        for level in [
+            OutputLevel::Terse,
+            OutputLevel::Normal,
+            OutputLevel::Verbose,
+            OutputLevel::VeryVerbose,
+        ] {
+            assert_eq!(
+                OutputLevel::from(sys::DDCA_Output_Level::from(level)),
+                level
+            );
+        }
+
+        for level in [
+            SyslogLevel::NotSet,
+            SyslogLevel::Never,
+            SyslogLevel::Error,
+            SyslogLevel::Warning,
+            SyslogLevel::Notice,
+            SyslogLevel::Info,
+            SyslogLevel::Verbose,
+            SyslogLevel::Debug,
+        ] {
+            assert_eq!(
+                SyslogLevel::from(sys::DDCA_Syslog_Level::from(level)),
+                level
+            );
+        }
+    }
+}
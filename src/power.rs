@@ -0,0 +1,245 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{
+    DdcError, DdcErrorKind, Display, DisplayGroup, DisplayPath, Result, VcpBackend, VcpValue,
+    get_display_info_list, sys,
+};
+
+/// VCP feature code for the power mode.
+const POWER_MODE: u8 = 0xD6;
+
+/// Power mode of a display (VCP feature 0xD6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerMode {
+    On,
+    /// DPMS standby.
+    Standby,
+    /// DPMS suspend.
+    Suspend,
+    /// DPMS off. The display still answers DDC/CI and can be woken up.
+    Off,
+    /// Like pressing the power button. Many displays can't be turned back on over DDC/CI from
+    /// this state.
+    HardOff,
+}
+
+impl PowerMode {
+    pub fn code(self) -> u8 {
+        match self {
+            PowerMode::On => 0x01,
+            PowerMode::Standby => 0x02,
+            PowerMode::Suspend => 0x03,
+            PowerMode::Off => 0x04,
+            PowerMode::HardOff => 0x05,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0x01 => PowerMode::On,
+            0x02 => PowerMode::Standby,
+            0x03 => PowerMode::Suspend,
+            0x04 => PowerMode::Off,
+            0x05 => PowerMode::HardOff,
+            _ => return None,
+        })
+    }
+}
+
+/// Options for [`Display::wake`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeOptions {
+    /// How long to keep trying before giving up.
+    pub timeout: Duration,
+    /// Time between attempts. Displays coming out of standby often take a few seconds before
+    /// they answer again.
+    pub interval: Duration,
+}
+
+impl Default for WakeOptions {
+    fn default() -> Self {
+        WakeOptions {
+            timeout: Duration::from_secs(30),
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Read the power mode from feature 0xD6.
+fn read_power_mode<B: VcpBackend + ?Sized>(backend: &B) -> Result<PowerMode> {
+    let code = match backend.get_vcp_value(POWER_MODE)? {
+        VcpValue::NonContinuous { sl, .. } => sl,
+        VcpValue::Continuous { current, .. } => current as u8,
+        VcpValue::Table(_) => 0,
+    };
+    PowerMode::from_code(code).ok_or_else(|| DdcError::from_kind_rc(sys::DDCRC_BAD_DATA))
+}
+
+/// See [`Display::wake`].
+fn wake<B: VcpBackend + ?Sized>(backend: &B, opts: &WakeOptions) -> Result<()> {
+    let start = Instant::now();
+    loop {
+        let state = backend
+            .set_vcp_value(POWER_MODE, PowerMode::On.code().into())
+            .and_then(|()| read_power_mode(backend));
+        let err = match state {
+            Ok(PowerMode::On) => return Ok(()),
+            Ok(_) => DdcError::from_kind_rc(sys::DDCRC_VERIFY),
+            Err(e) => e,
+        };
+        if start.elapsed() >= opts.timeout {
+            return Err(err);
+        }
+        thread::sleep(opts.interval);
+    }
+}
+
+impl Display {
+    /// Whether the display is in a DPMS sleep mode, as reported by the kernel.
+    ///
+    /// Sleeping displays often don't answer DDC/CI requests, so it's worth checking this before
+    /// reading features.
+    pub fn is_asleep(&self) -> Result<bool> {
+        match self.get_display_ref().validate(true) {
+            Ok(()) => Ok(false),
+            Err(e) if e.kind() == DdcErrorKind::DpmsAsleep => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the power mode.
+    ///
+    /// If the display doesn't answer and [is asleep](Self::is_asleep), this fails with
+    /// `DDCRC_DPMS_ASLEEP`. Values outside the MCCS range fail with `DDCRC_BAD_DATA`.
+    pub fn power_state(&self) -> Result<PowerMode> {
+        read_power_mode(self).map_err(|e| match self.is_asleep() {
+            Ok(true) => DdcError::from_kind_rc(sys::DDCRC_DPMS_ASLEEP),
+            _ => e,
+        })
+    }
+
+    pub fn set_power_state(&self, mode: PowerMode) -> Result<()> {
+        self.set_vcp_value(POWER_MODE, mode.code().into())
+    }
+
+    /// Turn the display on and wait until it reports [`PowerMode::On`].
+    ///
+    /// The mode is written again on every attempt, since displays in standby may miss the first
+    /// request. On timeout, this fails with the last error, or `DDCRC_VERIFY` if the display
+    /// answered with another mode.
+    ///
+    /// This can't wake a display that the compositor put to sleep through DPMS; that has to be
+    /// done by the compositor.
+    pub fn wake(&self, opts: &WakeOptions) -> Result<()> {
+        wake(self, opts)
+    }
+}
+
+impl<B: VcpBackend + Send> DisplayGroup<B> {
+    /// Wake all displays in parallel, see [`Display::wake`].
+    pub fn wake(&mut self, opts: &WakeOptions) -> Vec<Result<()>> {
+        self.for_each(|m| wake(&m.display, opts))
+    }
+}
+
+/// Wake all connected displays in parallel and wait until they respond, see [`Display::wake`].
+///
+/// Displays that don't answer DDC/CI during detection are included, since a display that is
+/// switched off may not. Returns the result for each display, in detection order.
+pub fn wake_all(opts: &WakeOptions) -> Result<Vec<(DisplayPath, Result<()>)>> {
+    let list = get_display_info_list(true)?;
+    let targets: Vec<_> = list
+        .as_slice()
+        .iter()
+        .map(|info| (info.path(), Display::from_display_info(info)))
+        .collect();
+
+    Ok(thread::scope(|s| {
+        let handles: Vec<_> = targets
+            .into_iter()
+            .map(|(path, display)| s.spawn(move || (path, display.and_then(|d| d.wake(opts)))))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn mode_codes() {
+        for code in 0x01..=0x05 {
+            assert_eq!(PowerMode::from_code(code).unwrap().code(), code);
+        }
+        assert_eq!(PowerMode::from_code(0x00), None);
+        assert_eq!(PowerMode::from_code(0x06), None);
+    }
+
+    /// A display that ignores the first writes while it comes out of standby.
+    struct Sleepy {
+        mode: Cell<u8>,
+        ignored_writes: Cell<u32>,
+    }
+
+    impl VcpBackend for Sleepy {
+        fn get_vcp_value(&self, code: u8) -> Result<VcpValue> {
+            assert_eq!(code, POWER_MODE);
+            Ok(VcpValue::NonContinuous {
+                sh: 0,
+                sl: self.mode.get(),
+            })
+        }
+
+        fn set_vcp_value(&self, code: u8, value: u16) -> Result<()> {
+            assert_eq!(code, POWER_MODE);
+            match self.ignored_writes.get() {
+                0 => self.mode.set(value as u8),
+                n => self.ignored_writes.set(n - 1),
+            }
+            Ok(())
+        }
+
+        fn get_capabilities_string(&self) -> Result<String> {
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    fn wake_from_standby() {
+        let display = Sleepy {
+            mode: Cell::new(PowerMode::Standby.code()),
+            ignored_writes: Cell::new(2),
+        };
+        assert_eq!(read_power_mode(&display).unwrap(), PowerMode::Standby);
+
+        let opts = WakeOptions {
+            timeout: Duration::from_secs(5),
+            interval: Duration::ZERO,
+        };
+        let start = Instant::now();
+        wake(&display, &opts).unwrap();
+        assert!(start.elapsed() < opts.timeout);
+        assert_eq!(read_power_mode(&display).unwrap(), PowerMode::On);
+
+        // a display that never wakes up times out with a verify error
+        let display = Sleepy {
+            mode: Cell::new(PowerMode::Off.code()),
+            ignored_writes: Cell::new(u32::MAX),
+        };
+        let opts = WakeOptions {
+            timeout: Duration::ZERO,
+            ..opts
+        };
+        assert_eq!(
+            wake(&display, &opts).unwrap_err().kind(),
+            DdcErrorKind::Verify
+        );
+    }
+}
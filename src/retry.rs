@@ -0,0 +1,166 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::thread;
+use std::time::Duration;
+
+use crate::err::{DdcError, DdcErrorKind, Result};
+
+/// Policy for retrying operations that fail with transient DDC errors.
+///
+/// DDC/CI communication is unreliable, and many displays occasionally fail to respond. A policy
+/// can be attached to a [`crate::Display`] with [`crate::Display::set_retry_policy`], which
+/// applies it to all VCP reads and writes on that display, or used for a single call with
+/// [`RetryPolicy::run`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one. `1` disables retrying.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts.
+    pub max_backoff: Duration,
+    /// Factor the delay is multiplied by after each attempt.
+    pub multiplier: f64,
+    /// Fraction of each delay that is randomized, between 0.0 (no jitter) and 1.0.
+    pub jitter: f64,
+    /// Errors that are retried. Any other error is returned immediately. `None` retries the
+    /// transient errors, see [`DdcErrorKind::is_transient`].
+    pub retryable: Option<Vec<DdcErrorKind>>,
+}
+
+impl Default for RetryPolicy {
+    /// Retry transient errors (see [`DdcErrorKind::is_transient`]) up to 3 attempts in total.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+            multiplier: 2.0,
+            jitter: 0.25,
+            retryable: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that never retries.
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_retryable(&self, err: &DdcError) -> bool {
+        match &self.retryable {
+            Some(kinds) => kinds.contains(&err.kind()),
+            None => err.kind().is_transient(),
+        }
+    }
+
+    /// Delay to wait after the given (1-based) failed attempt, including jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        let base = self
+            .initial_backoff
+            .mul_f64(exp)
+            .min(self.max_backoff)
+            .as_secs_f64();
+
+        // random factor in [-jitter, jitter]
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let rand = RandomState::new().hash_one(attempt) as f64 / u64::MAX as f64;
+        let factor = 1.0 + jitter * (2.0 * rand - 1.0);
+
+        Duration::from_secs_f64((base * factor).max(0.0))
+    }
+
+    /// Run `op`, retrying according to this policy.
+    ///
+    /// If all attempts fail, the last error is returned with [`DdcError::attempts`] set.
+    pub fn run<T>(&self, mut op: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Ok(v) => return Ok(v),
+                Err(e) if attempt < self.max_attempts && self.is_retryable(&e) => {
+                    thread::sleep(self.backoff(attempt));
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.with_attempts(attempt)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sys;
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            multiplier: 2.0,
+            jitter: 0.0,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+        assert_eq!(policy.backoff(10), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_backoff_jitter_in_range() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            jitter: 0.5,
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            let d = policy.backoff(1);
+            assert!(d >= Duration::from_millis(50) && d <= Duration::from_millis(150));
+        }
+    }
+
+    #[test]
+    fn test_run_counts_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::ZERO,
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let failing = |failures: u32, rc: i32| {
+            let mut calls = 0;
+            let result = policy.run(|| {
+                calls += 1;
+                if calls <= failures {
+                    Err(DdcError::from_kind_rc(rc))
+                } else {
+                    Ok(calls)
+                }
+            });
+            (result, calls)
+        };
+
+        // succeeds on the third attempt
+        let (result, calls) = failing(2, sys::DDCRC_NULL_RESPONSE);
+        assert_eq!((result.unwrap(), calls), (3, 3));
+
+        // gives up after max_attempts
+        let (result, calls) = failing(10, sys::DDCRC_NULL_RESPONSE);
+        assert_eq!(calls, 4);
+        assert_eq!(result.unwrap_err().attempts(), 4);
+
+        // non-transient errors are not retried
+        let (result, calls) = failing(10, sys::DDCRC_ARG);
+        assert_eq!(calls, 1);
+        assert_eq!(result.unwrap_err().attempts(), 1);
+    }
+}
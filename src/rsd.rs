@@ -0,0 +1,565 @@
+//! Daemon serving displays over newline-delimited JSON-RPC 2.0 on a Unix socket.
+//!
+//! A single daemon owns all display handles, so that tools using it don't interfere with each
+//! other on the I2C bus. Displays are addressed with a [`DisplaySelector`] string in the `display`
+//! parameter; it can be left out if there is only one display.
+//!
+//! | method         | params                    | result                              |
+//! |----------------|---------------------------|-------------------------------------|
+//! | `list`         |                           | array of display infos              |
+//! | `info`         | `display`                 | display info, including the EDID    |
+//! | `capabilities` | `display`                 | capabilities string                 |
+//! | `get`          | `display`, `code`         | [`VcpValue`]                        |
+//! | `set`          | `display`, `code`, `value`| `null`                              |
+//! | `subscribe`    |                           | `null`, then `event` notifications  |
+//!
+//! Clients are identified with `SO_PEERCRED` and checked against an [`AccessPolicy`]; `set` needs
+//! write access, everything else read access.
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde_json::{Value, json};
+
+use crate::display::{Display, VcpValue};
+use crate::display_info::{DisplayInfo, DisplayInfoList, DisplayPath, get_display_info_list};
+use crate::err::DdcError;
+use crate::events::{self, DisplayEvent, DisplayEventType, EventSubscription};
+use crate::selector::DisplaySelector;
+use crate::to_hex;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// A library call failed. The error data has the `rc` and its name.
+pub const DDC_ERROR: i64 = -32000;
+/// The client is not allowed to call the method.
+pub const PERMISSION_DENIED: i64 = -32001;
+
+/// Credentials of the process on the other end of a Unix socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCred {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl PeerCred {
+    pub fn of(stream: &UnixStream) -> io::Result<Self> {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = size_of::<libc::ucred>() as libc::socklen_t;
+
+        let rc = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if rc != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(PeerCred {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+        })
+    }
+
+    /// Supplementary groups of the peer process, from `/proc`. Empty if they can't be read.
+    fn groups(&self) -> Vec<u32> {
+        std::fs::read_to_string(format!("/proc/{0}/status", self.pid))
+            .ok()
+            .and_then(|status| {
+                status
+                    .lines()
+                    .find_map(|l| l.strip_prefix("Groups:"))
+                    .map(|g| {
+                        g.split_whitespace()
+                            .filter_map(|n| n.parse().ok())
+                            .collect()
+                    })
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Set of clients allowed to do something. Root is always allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Access {
+    pub anyone: bool,
+    pub uids: Vec<u32>,
+    /// Groups, matched against the primary and supplementary groups of the client.
+    pub gids: Vec<u32>,
+}
+
+impl Access {
+    pub fn anyone() -> Self {
+        Access {
+            anyone: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn uid(uid: u32) -> Self {
+        Access {
+            uids: vec![uid],
+            ..Default::default()
+        }
+    }
+
+    pub fn allows(&self, cred: &PeerCred) -> bool {
+        self.anyone
+            || cred.uid == 0
+            || self.uids.contains(&cred.uid)
+            || self.gids.contains(&cred.gid)
+            || (!self.gids.is_empty() && cred.groups().iter().any(|g| self.gids.contains(g)))
+    }
+}
+
+/// Which clients may read and change display settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessPolicy {
+    pub read: Access,
+    pub write: Access,
+}
+
+/// Anyone can read, only the user running the daemon can write.
+impl Default for AccessPolicy {
+    fn default() -> Self {
+        AccessPolicy {
+            read: Access::anyone(),
+            write: Access::uid(unsafe { libc::geteuid() }),
+        }
+    }
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<DdcError> for RpcError {
+    fn from(e: DdcError) -> Self {
+        RpcError {
+            code: DDC_ERROR,
+            message: e.to_string(),
+            data: Some(json!({ "rc": e.rc(), "kind": format!("{0:?}", e.kind()) })),
+        }
+    }
+}
+
+fn info_json(info: &DisplayInfo) -> Value {
+    let (kind, number) = match info.path() {
+        DisplayPath::I2C { bus } => ("i2c", bus),
+        DisplayPath::USB { hiddev_devno } => ("usb", hiddev_devno),
+    };
+
+    json!({
+        "display_no": info.display_no(),
+        "io_path": { kind: number },
+        "manufacturer": info.manufacturer(),
+        "model": info.model(),
+        "serial_number": info.serial_number(),
+        "product_code": info.product_code(),
+        "mccs_version": info.vcp_version().to_string(),
+    })
+}
+
+fn event_json(event: &DisplayEvent) -> Value {
+    let event_type = match event.event_type {
+        DisplayEventType::Connected => "connected",
+        DisplayEventType::Disconnected => "disconnected",
+        DisplayEventType::Awake => "awake",
+        DisplayEventType::Asleep => "asleep",
+        DisplayEventType::DdcEnabled => "ddc_enabled",
+    };
+    let io_path = match event.path {
+        DisplayPath::I2C { bus } => json!({ "i2c": bus }),
+        DisplayPath::USB { hiddev_devno } => json!({ "usb": hiddev_devno }),
+    };
+
+    json!({
+        "jsonrpc": "2.0",
+        "method": "event",
+        "params": { "type": event_type, "io_path": io_path },
+    })
+}
+
+fn param<'p, T: serde::Deserialize<'p>>(params: &'p Value, name: &str) -> Result<T, RpcError> {
+    let value = params
+        .get(name)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing parameter `{name}`")))?;
+    T::deserialize(value)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid parameter `{name}`: {e}")))
+}
+
+/// Number of events queued for a subscriber before it is dropped as too slow.
+const EVENT_QUEUE: usize = 64;
+
+/// The write half of a client connection, shared by responses and events so that lines are
+/// never interleaved.
+type Writer = Arc<Mutex<UnixStream>>;
+
+/// Write one line to a client with a single `write_all`.
+fn write_line(writer: &Writer, line: &str) -> io::Result<()> {
+    let mut buf = String::with_capacity(line.len() + 1);
+    buf.push_str(line);
+    buf.push('\n');
+    writer
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .write_all(buf.as_bytes())
+}
+
+/// The handle of a display, with the EDID of the display it is for. The handle is `None` until
+/// the display is first used.
+struct OpenDisplay {
+    edid: [u8; 128],
+    display: Arc<Mutex<Option<Display>>>,
+}
+
+/// The daemon state: open display handles and event subscribers.
+pub struct Daemon {
+    policy: AccessPolicy,
+    displays: Mutex<HashMap<DisplayPath, OpenDisplay>>,
+    /// Event queues of subscribed clients, each drained by a thread of its own.
+    subscribers: Mutex<Vec<(u64, SyncSender<String>)>>,
+    next_client: AtomicU64,
+}
+
+impl Daemon {
+    pub fn new(policy: AccessPolicy) -> Arc<Self> {
+        Arc::new(Daemon {
+            policy,
+            displays: Mutex::new(HashMap::new()),
+            subscribers: Mutex::new(Vec::new()),
+            next_client: AtomicU64::new(0),
+        })
+    }
+
+    pub fn policy(&self) -> &AccessPolicy {
+        &self.policy
+    }
+
+    /// Forward display events to subscribed clients, and drop the handles of displays that were
+    /// connected or disconnected. Display watching must be started with [`events::start_watch_displays`].
+    ///
+    /// Events are queued per client, so a client that stops reading doesn't hold up the others.
+    /// A client that falls too far behind is unsubscribed.
+    pub fn watch(self: &Arc<Self>) -> crate::Result<EventSubscription> {
+        let daemon = Arc::downgrade(self);
+        events::subscribe(move |event| {
+            let Some(daemon) = daemon.upgrade() else {
+                return;
+            };
+
+            if matches!(
+                event.event_type,
+                DisplayEventType::Connected | DisplayEventType::Disconnected
+            ) {
+                daemon.lock_displays().remove(&event.path);
+            }
+
+            let line = event_json(event).to_string();
+            let mut subscribers = daemon.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+            subscribers.retain(|(_, queue)| match queue.try_send(line.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+            });
+        })
+    }
+
+    /// Accept clients until the listener fails, serving each on its own thread.
+    pub fn serve(self: &Arc<Self>, listener: &UnixListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let daemon = self.clone();
+            std::thread::spawn(move || daemon.serve_client(stream));
+        }
+    }
+
+    fn serve_client(&self, stream: UnixStream) -> io::Result<()> {
+        let cred = PeerCred::of(&stream)?;
+        let id = self.next_client.fetch_add(1, Ordering::Relaxed);
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+
+        let ret = BufReader::new(&stream).lines().try_for_each(|line| {
+            let line = line?;
+            if line.trim().is_empty() {
+                return Ok(());
+            }
+            match self.handle(id, &cred, &line, &writer) {
+                Some(resp) => write_line(&writer, &resp.to_string()),
+                None => Ok(()),
+            }
+        });
+
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(c, _)| *c != id);
+        ret
+    }
+
+    fn lock_displays(&self) -> MutexGuard<'_, HashMap<DisplayPath, OpenDisplay>> {
+        self.displays.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Handle one request line from a client. Returns the response, or `None` for notifications.
+    fn handle(&self, client: u64, cred: &PeerCred, line: &str, writer: &Writer) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(r) => r,
+            Err(e) => {
+                return Some(error_response(
+                    Value::Null,
+                    RpcError::new(PARSE_ERROR, e.to_string()),
+                ));
+            }
+        };
+
+        let id = request.get("id").cloned();
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some(method) => {
+                let params = request.get("params").cloned().unwrap_or(Value::Null);
+                self.call(client, cred, method, &params, writer)
+            }
+            None => Err(RpcError::new(INVALID_REQUEST, "missing method")),
+        };
+
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e),
+        })
+    }
+
+    fn call(
+        &self,
+        client: u64,
+        cred: &PeerCred,
+        method: &str,
+        params: &Value,
+        writer: &Writer,
+    ) -> Result<Value, RpcError> {
+        let access = match method {
+            "list" | "info" | "capabilities" | "get" | "subscribe" => &self.policy.read,
+            "set" => &self.policy.write,
+            _ => {
+                return Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("unknown method `{method}`"),
+                ));
+            }
+        };
+        if !access.allows(cred) {
+            return Err(RpcError::new(
+                PERMISSION_DENIED,
+                format!("uid {0} may not call `{method}`", cred.uid),
+            ));
+        }
+
+        match method {
+            "list" => {
+                let list = get_display_info_list(false)?;
+                Ok(list.as_slice().iter().map(info_json).collect())
+            }
+            "info" => self.with_info(params, |info| {
+                let mut json = info_json(info);
+                json["edid"] = to_hex(info.edid_bytes()).into();
+                Ok(json)
+            }),
+            "capabilities" => {
+                self.with_display(params, |d| Ok(d.get_capabilities_string()?.into()))
+            }
+            "get" => {
+                let code: u8 = param(params, "code")?;
+                self.with_display(params, |d| {
+                    let value: VcpValue = d.get_vcp_value(code)?;
+                    Ok(serde_json::to_value(value).expect("VcpValue serializes"))
+                })
+            }
+            "set" => {
+                let code: u8 = param(params, "code")?;
+                let value: u16 = param(params, "value")?;
+                self.with_display(params, |d| {
+                    Ok(d.set_vcp_value(code, value).map(|_| Value::Null)?)
+                })
+            }
+            "subscribe" => {
+                let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+                if subscribers.iter().any(|(c, _)| *c == client) {
+                    return Ok(Value::Null);
+                }
+
+                let (queue, events) = mpsc::sync_channel::<String>(EVENT_QUEUE);
+                let writer = writer.clone();
+                // ends when the queue is dropped, i.e. the client disconnects or falls behind
+                std::thread::spawn(move || {
+                    for line in events {
+                        if write_line(&writer, &line).is_err() {
+                            break;
+                        }
+                    }
+                });
+                subscribers.push((client, queue));
+                Ok(Value::Null)
+            }
+            _ => unreachable!("checked above"),
+        }
+    }
+
+    fn with_info<R>(
+        &self,
+        params: &Value,
+        f: impl FnOnce(&DisplayInfo) -> Result<R, RpcError>,
+    ) -> Result<R, RpcError> {
+        let selector: DisplaySelector = match params.get("display") {
+            Some(_) => param::<&str>(params, "display")?
+                .parse()
+                .map_err(|e| RpcError::new(INVALID_PARAMS, format!("{e}")))?,
+            None => DisplaySelector::default(),
+        };
+
+        let list: DisplayInfoList = get_display_info_list(false)?;
+        // drop the handles of displays that are gone, in case an event was missed
+        self.lock_displays()
+            .retain(|path, _| list.into_iter().any(|i| i.path() == *path));
+
+        let info = selector
+            .resolve_one(&list)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, format!("{e}")))?;
+        f(info)
+    }
+
+    /// Run `f` with the display selected by `params`, opening it if needed. Calls on the same
+    /// display are serialized across clients.
+    ///
+    /// A handle is reused only for the display it was opened for: if another monitor shows up
+    /// on the same bus, it is opened again.
+    fn with_display<R>(
+        &self,
+        params: &Value,
+        f: impl FnOnce(&Display) -> Result<R, RpcError>,
+    ) -> Result<R, RpcError> {
+        self.with_info(params, |info| {
+            let edid = *info.edid_bytes();
+            let slot = {
+                let mut displays = self.lock_displays();
+                match displays.get(&info.path()) {
+                    Some(open) if open.edid == edid => open.display.clone(),
+                    _ => {
+                        let slot = Arc::new(Mutex::new(None));
+                        let open = OpenDisplay {
+                            edid,
+                            display: slot.clone(),
+                        };
+                        displays.insert(info.path(), open);
+                        slot
+                    }
+                }
+            };
+
+            // opening talks to the display, so it happens without holding up other displays
+            let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+            let display = match &mut *slot {
+                Some(display) => display,
+                empty => empty.insert(Display::from_display_info(info)?),
+            };
+            f(display)
+        })
+    }
+}
+
+fn error_response(id: Value, e: RpcError) -> Value {
+    let mut error = json!({ "code": e.code, "message": e.message });
+    if let Some(data) = e.data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn errors_and_permissions() {
+        let daemon = Daemon::new(AccessPolicy {
+            read: Access::anyone(),
+            write: Access::uid(1000),
+        });
+        let (stream, other) = UnixStream::pair().unwrap();
+        let writer = Arc::new(Mutex::new(stream));
+        let guest = PeerCred {
+            pid: 0,
+            uid: 1001,
+            gid: 1001,
+        };
+        let error_code = |line: &str| {
+            let resp = daemon.handle(0, &guest, line, &writer).unwrap();
+            resp["error"]["code"].as_i64()
+        };
+
+        assert_eq!(error_code("{"), Some(PARSE_ERROR));
+        assert_eq!(
+            error_code(r#"{"jsonrpc":"2.0","id":1}"#),
+            Some(INVALID_REQUEST)
+        );
+        assert_eq!(
+            error_code(r#"{"jsonrpc":"2.0","id":1,"method":"reset"}"#),
+            Some(METHOD_NOT_FOUND)
+        );
+        assert_eq!(
+            error_code(r#"{"jsonrpc":"2.0","id":1,"method":"set","params":{"code":16,"value":5}}"#),
+            Some(PERMISSION_DENIED)
+        );
+        assert_eq!(
+            daemon.handle(0, &guest, r#"{"jsonrpc":"2.0","method":"set"}"#, &writer),
+            None
+        );
+
+        let resp = daemon
+            .handle(
+                0,
+                &guest,
+                r#"{"jsonrpc":"2.0","id":"s","method":"subscribe"}"#,
+                &writer,
+            )
+            .unwrap();
+        assert_eq!(resp["id"], "s");
+        assert_eq!(resp["result"], Value::Null);
+
+        // events go through the client's queue and arrive as whole lines
+        let event = r#"{"jsonrpc":"2.0","method":"event"}"#;
+        for (_, queue) in daemon.subscribers.lock().unwrap().iter() {
+            queue.try_send(event.to_owned()).unwrap();
+        }
+        let mut line = String::new();
+        BufReader::new(&other).read_line(&mut line).unwrap();
+        assert_eq!(line, format!("{event}\n"));
+    }
+}
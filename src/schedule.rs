@@ -0,0 +1,485 @@
+//! Time-of-day scheduling of monitor presets.
+//!
+//! A [`Schedule`] lists [`Preset`]s and the times they apply: a fixed local time, or sunrise or
+//! sunset at a [`Location`], computed locally. A [`Scheduler`] applies the active preset to a
+//! [`DisplayGroup`] with a transition, and catches up on entries it missed while the machine was
+//! suspended or the scheduler wasn't running.
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::backend::VcpBackend;
+use crate::display::Display;
+use crate::err::{DdcError, Result};
+use crate::group::DisplayGroup;
+use crate::transition::{CancelToken, Easing};
+
+const BRIGHTNESS: u8 = 0x10;
+const CONTRAST: u8 = 0x12;
+const COLOR_PRESET: u8 = 0x14;
+const RED_GAIN: u8 = 0x16;
+const GREEN_GAIN: u8 = 0x18;
+const BLUE_GAIN: u8 = 0x1A;
+
+const DAY: i64 = 86400;
+
+/// Longest time the scheduler sleeps before checking the wall clock again.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// Settings applied together. Continuous features are logical percentages (0-100), mapped onto
+/// each display with its [`Adjustment`](crate::Adjustment); unset features are left alone.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Preset {
+    pub name: String,
+    pub brightness: Option<f64>,
+    pub contrast: Option<f64>,
+    /// Raw value of the color preset feature (0x14), e.g. `0x05` for 6500K on most monitors.
+    pub color_preset: Option<u8>,
+    pub red_gain: Option<f64>,
+    pub green_gain: Option<f64>,
+    pub blue_gain: Option<f64>,
+}
+
+impl Preset {
+    /// Apply the preset to a group, spreading `duration` over the continuous features that are
+    /// set. The color preset is applied first, since it resets the gains on many monitors.
+    ///
+    /// Returns the first error from any display.
+    pub fn apply<B: VcpBackend + Send>(
+        &self,
+        group: &mut DisplayGroup<B>,
+        duration: Duration,
+        easing: Easing,
+        cancel: &CancelToken,
+    ) -> Result<()> {
+        if let Some(value) = self.color_preset {
+            group
+                .set_vcp_value(COLOR_PRESET, value.into())
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?;
+        }
+
+        let continuous: Vec<_> = [
+            (RED_GAIN, self.red_gain),
+            (GREEN_GAIN, self.green_gain),
+            (BLUE_GAIN, self.blue_gain),
+            (CONTRAST, self.contrast),
+            (BRIGHTNESS, self.brightness),
+        ]
+        .into_iter()
+        .filter_map(|(code, percent)| Some((code, percent?)))
+        .collect();
+
+        let step = duration / continuous.len().max(1) as u32;
+        for (code, percent) in continuous {
+            group
+                .transition_percent(code, percent, step, easing, cancel)
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Position on Earth, in degrees. Latitude is positive to the north, longitude to the east.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Sunrise and sunset on a day, as Unix timestamps.
+///
+/// `day` is the local date as a number of days since 1970-01-01. Returns `None` if the sun does
+/// not rise or set that day (polar day or night). Uses the NOAA sunrise equation, which is
+/// accurate to about a minute outside the polar regions.
+pub fn sun_times(day: i64, location: Location) -> Option<(i64, i64)> {
+    let (sin, cos) = (
+        |deg: f64| deg.to_radians().sin(),
+        |deg: f64| deg.to_radians().cos(),
+    );
+
+    // days since J2000 (2000-01-01 12:00 UTC), and mean solar time at the longitude
+    let n = (day - 10957) as f64;
+    let j_star = n - location.longitude / 360.0;
+
+    let m = (357.5291 + 0.98560028 * j_star).rem_euclid(360.0);
+    let c = 1.9148 * sin(m) + 0.0200 * sin(2.0 * m) + 0.0003 * sin(3.0 * m);
+    let lambda = (m + c + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = j_star + 0.0053 * sin(m) - 0.0069 * sin(2.0 * lambda);
+
+    let sin_decl = sin(lambda) * sin(23.4397);
+    let cos_decl = (1.0 - sin_decl * sin_decl).sqrt();
+    let cos_hour_angle =
+        (sin(-0.833) - sin(location.latitude) * sin_decl) / (cos(location.latitude) * cos_decl);
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    // days since J2000 to Unix time
+    let unix = |j: f64| ((j + 10957.5) * DAY as f64).round() as i64;
+    Some((
+        unix(transit - hour_angle / 360.0),
+        unix(transit + hour_angle / 360.0),
+    ))
+}
+
+/// Offset of local time from UTC at Unix time `t`, in seconds.
+// time_t and c_long are 32 bits wide on some targets, so the conversions aren't always no-ops
+#[allow(clippy::useless_conversion, clippy::unnecessary_fallible_conversions)]
+fn local_offset(t: i64) -> i64 {
+    let Some(t) = libc::time_t::try_from(t).ok() else {
+        return 0;
+    };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&t, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff.into()
+}
+
+/// Error from parsing a [`TimeOfDay`] or validating a [`Schedule`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    InvalidTime(String),
+    UnknownPreset(String),
+    /// A sunrise or sunset entry needs the schedule's location.
+    MissingLocation,
+}
+
+impl std::fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::InvalidTime(s) => write!(f, "invalid time of day: {s:?}"),
+            ScheduleError::UnknownPreset(name) => write!(f, "unknown preset: {name:?}"),
+            ScheduleError::MissingLocation => {
+                write!(f, "sunrise and sunset entries need a location")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// Local wall-clock time, e.g. "07:30".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TimeOfDay {
+    fn seconds(&self) -> i64 {
+        self.hour as i64 * 3600 + self.minute as i64 * 60
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let err = || ScheduleError::InvalidTime(s.to_owned());
+        let (hour, minute) = s.trim().split_once(':').ok_or_else(err)?;
+        let time = TimeOfDay {
+            hour: hour.parse().map_err(|_| err())?,
+            minute: minute.parse().map_err(|_| err())?,
+        };
+        if time.hour > 23 || time.minute > 59 {
+            return Err(err());
+        }
+        Ok(time)
+    }
+}
+
+impl std::fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0:02}:{1:02}", self.hour, self.minute)
+    }
+}
+
+/// When a schedule entry applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Trigger {
+    At(TimeOfDay),
+    /// Minutes after sunrise, or before if negative.
+    Sunrise {
+        offset_minutes: i32,
+    },
+    /// Minutes after sunset, or before if negative.
+    Sunset {
+        offset_minutes: i32,
+    },
+}
+
+/// A preset to apply at a time each day.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScheduleEntry {
+    pub trigger: Trigger,
+    /// Name of the preset in [`Schedule::presets`].
+    pub preset: String,
+    /// Duration of the transition to the preset, in seconds.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub transition_secs: u32,
+}
+
+/// A scheduled entry on a particular day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Occurrence<'a> {
+    pub entry: &'a ScheduleEntry,
+    /// Unix time the entry applies.
+    pub time: i64,
+}
+
+/// Presets and the times they apply.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Schedule {
+    pub location: Option<Location>,
+    pub presets: Vec<Preset>,
+    pub entries: Vec<ScheduleEntry>,
+}
+
+impl Schedule {
+    /// Check that all entries refer to known presets, and that there is a location if needed.
+    pub fn validate(&self) -> std::result::Result<(), ScheduleError> {
+        for entry in &self.entries {
+            if self.preset(&entry.preset).is_none() {
+                return Err(ScheduleError::UnknownPreset(entry.preset.clone()));
+            }
+            if let Trigger::At(t) = entry.trigger
+                && (t.hour > 23 || t.minute > 59)
+            {
+                return Err(ScheduleError::InvalidTime(t.to_string()));
+            }
+            if !matches!(entry.trigger, Trigger::At(_)) && self.location.is_none() {
+                return Err(ScheduleError::MissingLocation);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&Preset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+
+    /// The entries on a local date (days since 1970-01-01), in schedule order.
+    ///
+    /// Sun entries are left out on days the sun doesn't rise or set, or without a location.
+    fn occurrences(&self, day: i64, offset: &impl Fn(i64) -> i64) -> Vec<Occurrence<'_>> {
+        let sun = self.location.and_then(|loc| sun_times(day, loc));
+
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let time = match entry.trigger {
+                    Trigger::At(t) => {
+                        let local = day * DAY + t.seconds();
+                        local - offset(local - offset(local))
+                    }
+                    Trigger::Sunrise { offset_minutes } => sun?.0 + offset_minutes as i64 * 60,
+                    Trigger::Sunset { offset_minutes } => sun?.1 + offset_minutes as i64 * 60,
+                };
+                Some(Occurrence { entry, time })
+            })
+            .collect()
+    }
+
+    fn local_day(now: i64, offset: &impl Fn(i64) -> i64) -> i64 {
+        (now + offset(now)).div_euclid(DAY)
+    }
+
+    fn active_with(&self, now: i64, offset: &impl Fn(i64) -> i64) -> Option<Occurrence<'_>> {
+        let today = Self::local_day(now, offset);
+        (today - 2..=today)
+            .flat_map(|day| self.occurrences(day, offset))
+            .filter(|o| o.time <= now)
+            .reduce(|a, b| if b.time >= a.time { b } else { a })
+    }
+
+    fn next_with(&self, now: i64, offset: &impl Fn(i64) -> i64) -> Option<Occurrence<'_>> {
+        let today = Self::local_day(now, offset);
+        (today..=today + 2)
+            .flat_map(|day| self.occurrences(day, offset))
+            .filter(|o| o.time > now)
+            .reduce(|a, b| if b.time < a.time { b } else { a })
+    }
+
+    /// The entry that should be in effect at Unix time `now`: the latest one at or before it.
+    pub fn active_at(&self, now: i64) -> Option<Occurrence<'_>> {
+        self.active_with(now, &local_offset)
+    }
+
+    /// The first entry after Unix time `now`.
+    pub fn next_after(&self, now: i64) -> Option<Occurrence<'_>> {
+        self.next_with(now, &local_offset)
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Applies a [`Schedule`] to a group of displays.
+#[derive(Debug)]
+pub struct Scheduler<'g, B = Display> {
+    schedule: Schedule,
+    group: &'g mut DisplayGroup<B>,
+    pub easing: Easing,
+    /// Time of the occurrence that was last applied.
+    applied: Option<i64>,
+}
+
+impl<'g, B: VcpBackend + Send> Scheduler<'g, B> {
+    pub fn new(
+        schedule: Schedule,
+        group: &'g mut DisplayGroup<B>,
+    ) -> std::result::Result<Self, ScheduleError> {
+        schedule.validate()?;
+        Ok(Scheduler {
+            schedule,
+            group,
+            easing: Easing::EaseInOut,
+            applied: None,
+        })
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Apply the active entry if it hasn't been applied yet, or if `force` is set.
+    ///
+    /// An entry that started less than its transition time ago only gets the remainder of the
+    /// transition, so catching up after a pause doesn't replay long fades.
+    pub fn update(&mut self, force: bool, cancel: &CancelToken) -> Result<()> {
+        let now = unix_now();
+        let Some(active) = self.schedule.active_at(now) else {
+            return Ok(());
+        };
+        if !force && self.applied == Some(active.time) {
+            return Ok(());
+        }
+
+        let preset = self
+            .schedule
+            .preset(&active.entry.preset)
+            .expect("validated in new");
+        let elapsed = Duration::from_secs((now - active.time).max(0) as u64);
+        let remaining =
+            Duration::from_secs(active.entry.transition_secs.into()).saturating_sub(elapsed);
+
+        preset.apply(self.group, remaining, self.easing, cancel)?;
+        self.applied = Some(active.time);
+        Ok(())
+    }
+
+    /// Apply entries as they come due until cancelled.
+    ///
+    /// The wall clock is checked at least every minute. After a suspend, which shows up as the
+    /// wall clock moving further than the monotonic clock, the active preset is applied again,
+    /// since monitors may have lost their settings while powered down. Failed updates are tried
+    /// again at the next check; `on_error` is called with the error.
+    pub fn run(&mut self, cancel: &CancelToken, mut on_error: impl FnMut(&DdcError)) {
+        let mut force = false;
+        while !cancel.is_cancelled() {
+            match self.update(force, cancel) {
+                Ok(()) => force = false,
+                Err(e) => on_error(&e),
+            }
+
+            let now = unix_now();
+            let until_next = self
+                .schedule
+                .next_after(now)
+                .map_or(MAX_SLEEP, |o| Duration::from_secs((o.time - now) as u64));
+
+            let (mono, wall) = (Instant::now(), unix_now());
+            std::thread::sleep(until_next.min(MAX_SLEEP));
+            let slept = mono.elapsed().as_secs() as i64;
+            force |= unix_now() - wall > slept + 30;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 2024-06-21
+    const MIDSUMMER: i64 = 19895;
+
+    #[test]
+    fn sunrise_sunset() {
+        let london = Location {
+            latitude: 51.5074,
+            longitude: -0.1278,
+        };
+        let (rise, set) = sun_times(MIDSUMMER, london).unwrap();
+        // 03:43 and 20:21 UTC
+        assert!((rise - (MIDSUMMER * DAY + 3 * 3600 + 43 * 60)).abs() < 180);
+        assert!((set - (MIDSUMMER * DAY + 20 * 3600 + 21 * 60)).abs() < 180);
+
+        let tromso = Location {
+            latitude: 69.65,
+            longitude: 18.96,
+        };
+        assert_eq!(sun_times(MIDSUMMER, tromso), None);
+    }
+
+    #[test]
+    fn active_entry() {
+        let entry = |trigger, preset: &str| ScheduleEntry {
+            trigger,
+            preset: preset.to_owned(),
+            transition_secs: 0,
+        };
+        let schedule = Schedule {
+            location: Some(Location {
+                latitude: 51.5074,
+                longitude: -0.1278,
+            }),
+            presets: vec![],
+            entries: vec![
+                entry(Trigger::At("07:00".parse().unwrap()), "day"),
+                entry(
+                    Trigger::Sunset {
+                        offset_minutes: -60,
+                    },
+                    "evening",
+                ),
+                entry(Trigger::At("23:30".parse().unwrap()), "night"),
+            ],
+        };
+        // British summer time
+        let bst = |_| 3600;
+        let at = |h: i64, m: i64| MIDSUMMER * DAY + h * 3600 + m * 60 - 3600;
+        let active = |t| schedule.active_with(t, &bst).unwrap().entry.preset.as_str();
+
+        assert_eq!(active(at(6, 59)), "night");
+        assert_eq!(active(at(7, 0)), "day");
+        assert_eq!(active(at(20, 30)), "evening");
+        assert_eq!(active(at(23, 45)), "night");
+        let (_, sunset) = sun_times(MIDSUMMER, schedule.location.unwrap()).unwrap();
+        let next = schedule.next_with(at(12, 0), &bst).unwrap();
+        assert_eq!(
+            (next.entry.preset.as_str(), next.time),
+            ("evening", sunset - 3600)
+        );
+
+        assert_eq!(
+            schedule.validate(),
+            Err(ScheduleError::UnknownPreset("day".to_owned()))
+        );
+        assert!("24:00".parse::<TimeOfDay>().is_err());
+    }
+}
@@ -0,0 +1,361 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::display_info::{DisplayInfo, DisplayInfoList, DisplayPath};
+use crate::to_hex;
+
+/// Errors from parsing or resolving a [`DisplaySelector`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorError {
+    /// The selector string is invalid.
+    Parse(String),
+    /// No display matches the selector.
+    NoMatch,
+    /// More than one display matches the selector.
+    Ambiguous(usize),
+}
+
+impl std::fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelectorError::Parse(msg) => write!(f, "invalid display selector: {msg}"),
+            SelectorError::NoMatch => write!(f, "no display matches the selector"),
+            SelectorError::Ambiguous(n) => write!(f, "{n} displays match the selector"),
+        }
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+/// Human-friendly way to select displays, e.g. in config files or command line flags.
+///
+/// A selector is a list of whitespace-separated `key=value` terms, all of which must match:
+///
+/// | key      | value                                                  |
+/// |----------|--------------------------------------------------------|
+/// | `bus`    | I2C bus number, e.g. `bus=6` for `/dev/i2c-6`          |
+/// | `dispno` | ddcutil display number                                 |
+/// | `mfg`    | manufacturer id, e.g. `mfg=DEL`                        |
+/// | `model`  | model name, e.g. `model="U2720Q"`                      |
+/// | `sn`     | serial number                                          |
+/// | `product`| product code, decimal or `0x` hex                      |
+/// | `edid`   | hex-encoded EDID, or a prefix of it                    |
+/// | `usb`    | USB `bus:device`, e.g. `usb=1:4`                       |
+///
+/// Values can be double-quoted to include whitespace. `mfg`, `model` and `sn` are matched
+/// case-insensitively and support `*` and `?` wildcards, e.g. `model="U27*"`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DisplaySelector {
+    pub bus: Option<i32>,
+    pub display_no: Option<i32>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub product_code: Option<u16>,
+    /// Lower-case hex EDID prefix
+    pub edid: Option<String>,
+    pub usb: Option<(i32, i32)>,
+}
+
+impl DisplaySelector {
+    /// Whether the display matches all terms of the selector.
+    pub fn matches(&self, info: &DisplayInfo) -> bool {
+        let path = info.path();
+
+        self.bus.is_none_or(|bus| path == DisplayPath::I2C { bus })
+            && self.display_no.is_none_or(|n| info.display_no() == n)
+            && self
+                .manufacturer
+                .as_deref()
+                .is_none_or(|p| glob_match(p, info.manufacturer()))
+            && self
+                .model
+                .as_deref()
+                .is_none_or(|p| glob_match(p, info.model()))
+            && self
+                .serial_number
+                .as_deref()
+                .is_none_or(|p| glob_match(p, info.serial_number()))
+            && self.product_code.is_none_or(|c| info.product_code() == c)
+            && self
+                .edid
+                .as_deref()
+                .is_none_or(|e| to_hex(info.edid_bytes()).starts_with(e))
+            && self.usb.is_none_or(|(bus, device)| {
+                info.usb_location()
+                    .is_some_and(|usb| usb.bus == bus && usb.device == device)
+            })
+    }
+
+    /// Find all displays in the list that match.
+    pub fn resolve<'a>(&self, list: &'a DisplayInfoList) -> Vec<&'a DisplayInfo> {
+        list.into_iter().filter(|i| self.matches(i)).collect()
+    }
+
+    /// Find the single display that matches, or fail if there are none or several.
+    pub fn resolve_one<'a>(
+        &self,
+        list: &'a DisplayInfoList,
+    ) -> Result<&'a DisplayInfo, SelectorError> {
+        match self.resolve(list).as_slice() {
+            [] => Err(SelectorError::NoMatch),
+            [info] => Ok(info),
+            all => Err(SelectorError::Ambiguous(all.len())),
+        }
+    }
+}
+
+/// Case-insensitive glob match supporting `*` and `?`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.to_lowercase().chars().collect();
+    let t: Vec<char> = text.to_lowercase().chars().collect();
+
+    // iterative matcher, backtracking to the last `*`
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Split a selector into `key=value` terms, handling double-quoted values.
+pub(crate) fn tokenize(s: &str) -> Result<Vec<(String, String)>, SelectorError> {
+    let mut terms = vec![];
+    let mut chars = s.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(terms);
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| c != '=' && !c.is_whitespace()) {
+            key.push(c);
+        }
+        if chars.next() != Some('=') {
+            return Err(SelectorError::Parse(format!("expected `=` after `{key}`")));
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c) => value.push(c),
+                        None => break,
+                    },
+                    Some(c) => value.push(c),
+                    None => {
+                        return Err(SelectorError::Parse(format!(
+                            "unterminated quote in value for `{key}`"
+                        )));
+                    }
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+
+        terms.push((key, value));
+    }
+}
+
+fn parse_num<T: FromStr>(key: &str, value: &str) -> Result<T, SelectorError> {
+    value
+        .parse()
+        .map_err(|_| SelectorError::Parse(format!("invalid number for `{key}`: {value:?}")))
+}
+
+impl FromStr for DisplaySelector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sel = DisplaySelector::default();
+
+        for (key, value) in tokenize(s)? {
+            match key.as_str() {
+                "bus" => sel.bus = Some(parse_num(&key, &value)?),
+                "dispno" => sel.display_no = Some(parse_num(&key, &value)?),
+                "mfg" => sel.manufacturer = Some(value),
+                "model" => sel.model = Some(value),
+                "sn" => sel.serial_number = Some(value),
+                "product" => {
+                    let code = match value.strip_prefix("0x") {
+                        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+                        None => value.parse().ok(),
+                    };
+                    sel.product_code = Some(code.ok_or_else(|| {
+                        SelectorError::Parse(format!("invalid product code: {value:?}"))
+                    })?);
+                }
+                "edid" => {
+                    if value.is_empty() || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+                        return Err(SelectorError::Parse(format!("invalid EDID: {value:?}")));
+                    }
+                    sel.edid = Some(value.to_ascii_lowercase());
+                }
+                "usb" => {
+                    let (bus, device) = value.split_once(':').ok_or_else(|| {
+                        SelectorError::Parse(format!("expected `usb=bus:device`, got {value:?}"))
+                    })?;
+                    sel.usb = Some((parse_num(&key, bus)?, parse_num(&key, device)?));
+                }
+                _ => return Err(SelectorError::Parse(format!("unknown key `{key}`"))),
+            }
+        }
+
+        if sel == DisplaySelector::default() {
+            return Err(SelectorError::Parse("empty selector".to_owned()));
+        }
+
+        Ok(sel)
+    }
+}
+
+/// Write a string value, quoting it if needed.
+pub(crate) fn write_value(f: &mut std::fmt::Formatter<'_>, value: &str) -> std::fmt::Result {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        return f.write_str(value);
+    }
+
+    f.write_char('"')?;
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+/// Formats the selector in the same syntax it is parsed from.
+impl std::fmt::Display for DisplaySelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut sep = "";
+        let mut term = |f: &mut std::fmt::Formatter<'_>, key: &str| {
+            let r = write!(f, "{sep}{key}=");
+            sep = " ";
+            r
+        };
+
+        if let Some(bus) = self.bus {
+            term(f, "bus")?;
+            write!(f, "{bus}")?;
+        }
+        if let Some(n) = self.display_no {
+            term(f, "dispno")?;
+            write!(f, "{n}")?;
+        }
+        if let Some(mfg) = &self.manufacturer {
+            term(f, "mfg")?;
+            write_value(f, mfg)?;
+        }
+        if let Some(model) = &self.model {
+            term(f, "model")?;
+            write_value(f, model)?;
+        }
+        if let Some(sn) = &self.serial_number {
+            term(f, "sn")?;
+            write_value(f, sn)?;
+        }
+        if let Some(code) = self.product_code {
+            term(f, "product")?;
+            write!(f, "{code}")?;
+        }
+        if let Some(edid) = &self.edid {
+            term(f, "edid")?;
+            f.write_str(edid)?;
+        }
+        if let Some((bus, device)) = self.usb {
+            term(f, "usb")?;
+            write!(f, "{bus}:{device}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let sel: DisplaySelector = r#"mfg=DEL model="U2720Q" sn=ABC123"#.parse().unwrap();
+        assert_eq!(
+            sel,
+            DisplaySelector {
+                manufacturer: Some("DEL".to_owned()),
+                model: Some("U2720Q".to_owned()),
+                serial_number: Some("ABC123".to_owned()),
+                ..Default::default()
+            }
+        );
+
+        let sel: DisplaySelector = "bus=6 usb=1:4 edid=00FFFF product=0x1a2b".parse().unwrap();
+        assert_eq!(sel.bus, Some(6));
+        assert_eq!(sel.usb, Some((1, 4)));
+        assert_eq!(sel.edid.as_deref(), Some("00ffff"));
+        assert_eq!(sel.product_code, Some(0x1a2b));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for s in [
+            "",
+            "bus",
+            "bus=x",
+            "usb=1",
+            "edid=xyz",
+            "color=red",
+            r#"model="U27"#,
+        ] {
+            assert!(
+                matches!(s.parse::<DisplaySelector>(), Err(SelectorError::Parse(_))),
+                "{s:?} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for s in [
+            "bus=6",
+            "dispno=2",
+            r#"mfg=DEL model="Dell U2720Q" sn="a\"b""#,
+            "edid=00ffffff usb=1:4",
+        ] {
+            let sel: DisplaySelector = s.parse().unwrap();
+            assert_eq!(sel.to_string(), s);
+            assert_eq!(sel.to_string().parse::<DisplaySelector>().unwrap(), sel);
+        }
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match("U2720Q", "u2720q"));
+        assert!(glob_match("U27*", "U2720Q"));
+        assert!(glob_match("*20?", "U2720Q"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("U27*X", "U2720Q"));
+        assert!(!glob_match("U2720", "U2720Q"));
+    }
+}
@@ -0,0 +1,492 @@
+//! Record-and-replay of DDC/CI sessions.
+//!
+//! A [`Recorder`] wraps a [`VcpBackend`], usually a [`Display`], and logs every call with its
+//! result and timing to a [`Trace`], which can be saved as JSON. A [`Replay`] serves a trace back
+//! in the same order, so a bug report from a monitor we don't have can become a regression test:
+//!
+//! ```no_run
+//! # use libddcutil2::{VcpBackend, trace::{Replay, Trace}};
+//! let replay = Replay::new(Trace::load("asus-brightness.json".as_ref())?);
+//! replay.set_vcp_value(0x10, 40)?;
+//! replay.finish()?;
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::VcpBackend;
+use crate::display::{Display, VcpValue};
+use crate::display_info::DisplayInfo;
+use crate::err::{DdcError, Result};
+use crate::sys;
+
+/// A call made on a backend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TraceOp {
+    GetVcp { code: u8 },
+    SetVcp { code: u8, value: u16 },
+    GetCapabilities,
+}
+
+impl std::fmt::Display for TraceOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceOp::GetVcp { code } => write!(f, "get 0x{code:02x}"),
+            TraceOp::SetVcp { code, value } => write!(f, "set 0x{code:02x} = {value}"),
+            TraceOp::GetCapabilities => write!(f, "get capabilities"),
+        }
+    }
+}
+
+/// What a call returned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceResult {
+    Value(VcpValue),
+    Capabilities(String),
+    /// A successful call without a value, i.e. a write.
+    Ok,
+    /// The call failed with this status code.
+    Error {
+        rc: i32,
+    },
+}
+
+/// One recorded call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    #[serde(flatten)]
+    pub op: TraceOp,
+    pub result: TraceResult,
+    /// Time from the start of the recording to the start of the call.
+    pub start_micros: u64,
+    pub duration_micros: u64,
+}
+
+/// The monitor a trace was recorded on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceDisplay {
+    pub manufacturer: String,
+    pub model: String,
+    pub serial_number: String,
+    pub product_code: u16,
+    pub mccs_version: String,
+}
+
+impl From<&DisplayInfo> for TraceDisplay {
+    fn from(info: &DisplayInfo) -> Self {
+        TraceDisplay {
+            manufacturer: info.manufacturer().to_owned(),
+            model: info.model().to_owned(),
+            serial_number: info.serial_number().to_owned(),
+            product_code: info.product_code(),
+            mccs_version: info.vcp_version().to_string(),
+        }
+    }
+}
+
+/// A recorded session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    pub display: Option<TraceDisplay>,
+    pub events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read(path)?;
+        serde_json::from_slice(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, contents)
+    }
+}
+
+fn micros(d: Duration) -> u64 {
+    d.as_micros().try_into().unwrap_or(u64::MAX)
+}
+
+/// Records the calls made through it to a [`Trace`].
+#[derive(Debug)]
+pub struct Recorder<B> {
+    inner: B,
+    start: Instant,
+    trace: Mutex<Trace>,
+}
+
+impl Recorder<Display> {
+    /// Record calls to a display, noting which monitor it is in the trace.
+    pub fn for_display(display: Display) -> Result<Self> {
+        let info = display.info()?;
+        let recorder = Self::new(display);
+        recorder.lock_trace().display = Some((&*info).into());
+        Ok(recorder)
+    }
+}
+
+impl<B: VcpBackend> Recorder<B> {
+    pub fn new(inner: B) -> Self {
+        Recorder {
+            inner,
+            start: Instant::now(),
+            trace: Mutex::new(Trace::default()),
+        }
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    fn lock_trace(&self) -> MutexGuard<'_, Trace> {
+        self.trace.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A copy of the calls recorded so far.
+    pub fn trace(&self) -> Trace {
+        self.lock_trace().clone()
+    }
+
+    pub fn into_trace(self) -> Trace {
+        self.trace.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record<T>(
+        &self,
+        op: TraceOp,
+        call: impl FnOnce() -> Result<T>,
+        to_result: impl FnOnce(&T) -> TraceResult,
+    ) -> Result<T> {
+        let start = Instant::now();
+        let ret = call();
+        let duration = start.elapsed();
+
+        let result = match &ret {
+            Ok(val) => to_result(val),
+            Err(e) => TraceResult::Error { rc: e.rc() },
+        };
+        self.lock_trace().events.push(TraceEvent {
+            op,
+            result,
+            start_micros: micros(start - self.start),
+            duration_micros: micros(duration),
+        });
+
+        ret
+    }
+}
+
+impl<B: VcpBackend> VcpBackend for Recorder<B> {
+    fn get_vcp_value(&self, code: u8) -> Result<VcpValue> {
+        self.record(
+            TraceOp::GetVcp { code },
+            || self.inner.get_vcp_value(code),
+            |v| TraceResult::Value(v.clone()),
+        )
+    }
+
+    fn set_vcp_value(&self, code: u8, value: u16) -> Result<()> {
+        self.record(
+            TraceOp::SetVcp { code, value },
+            || self.inner.set_vcp_value(code, value),
+            |_| TraceResult::Ok,
+        )
+    }
+
+    fn get_capabilities_string(&self) -> Result<String> {
+        self.record(
+            TraceOp::GetCapabilities,
+            || self.inner.get_capabilities_string(),
+            |c| TraceResult::Capabilities(c.clone()),
+        )
+    }
+}
+
+/// A call that did not match the trace being replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Index of the event in the trace, or the trace length if it was used up.
+    pub index: usize,
+    pub expected: Option<TraceOp>,
+    pub actual: TraceOp,
+}
+
+/// Error from [`Replay::finish`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// Calls were made that the trace did not expect.
+    Mismatch(Vec<Mismatch>),
+    /// The trace has calls left that were never made.
+    Unconsumed(usize),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Mismatch(m) => {
+                write!(f, "{0} calls did not match the trace", m.len())?;
+                if let Some(first) = m.first() {
+                    write!(f, ", first at event {0}: {1}", first.index, first.actual)?;
+                    match &first.expected {
+                        Some(expected) => write!(f, " instead of {expected}")?,
+                        None => write!(f, " after the end of the trace")?,
+                    }
+                }
+                Ok(())
+            }
+            ReplayError::Unconsumed(n) => write!(f, "{n} calls of the trace were not made"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[derive(Debug)]
+struct ReplayState {
+    events: VecDeque<TraceEvent>,
+    index: usize,
+    mismatches: Vec<Mismatch>,
+    /// When the first call was replayed, and the time it started in the trace.
+    origin: Option<(Instant, u64)>,
+}
+
+/// Serves the results of a [`Trace`] back, in order.
+///
+/// Each call must match the next event of the trace; calls that don't match fail with
+/// `DDCRC_INVALID_OPERATION` and are reported by [`finish`](Self::finish).
+#[derive(Debug)]
+pub struct Replay {
+    display: Option<TraceDisplay>,
+    state: Mutex<ReplayState>,
+    realtime: bool,
+}
+
+impl Replay {
+    pub fn new(trace: Trace) -> Self {
+        Replay {
+            display: trace.display,
+            state: Mutex::new(ReplayState {
+                events: trace.events.into(),
+                index: 0,
+                mismatches: Vec::new(),
+                origin: None,
+            }),
+            realtime: false,
+        }
+    }
+
+    /// Take as long as the recorded calls did, and keep the recorded gaps between them, e.g. to
+    /// reproduce timing issues. The first call starts the clock.
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    /// The monitor the trace was recorded on, if known.
+    pub fn display(&self) -> Option<&TraceDisplay> {
+        self.display.as_ref()
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, ReplayState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Check that every call matched the trace, and that the whole trace was used.
+    pub fn finish(&self) -> std::result::Result<(), ReplayError> {
+        let state = self.lock_state();
+        if !state.mismatches.is_empty() {
+            return Err(ReplayError::Mismatch(state.mismatches.clone()));
+        }
+        match state.events.len() {
+            0 => Ok(()),
+            n => Err(ReplayError::Unconsumed(n)),
+        }
+    }
+
+    fn next(&self, op: TraceOp) -> Result<TraceResult> {
+        let (event, origin) = {
+            let mut state = self.lock_state();
+            let index = state.index;
+            match state.events.front() {
+                Some(e) if e.op == op => {
+                    state.index += 1;
+                    let event = state.events.pop_front().expect("checked above");
+                    let origin = *state
+                        .origin
+                        .get_or_insert((Instant::now(), event.start_micros));
+                    (event, origin)
+                }
+                expected => {
+                    let expected = expected.map(|e| e.op.clone());
+                    state.mismatches.push(Mismatch {
+                        index,
+                        expected,
+                        actual: op,
+                    });
+                    return Err(DdcError::from_kind_rc(sys::DDCRC_INVALID_OPERATION));
+                }
+            }
+        };
+
+        if self.realtime {
+            let (start, start_micros) = origin;
+            let offset = event.start_micros.saturating_sub(start_micros);
+            let due = start + Duration::from_micros(offset);
+            std::thread::sleep(due.saturating_duration_since(Instant::now()));
+            std::thread::sleep(Duration::from_micros(event.duration_micros));
+        }
+        match event.result {
+            // the status code was recorded from the library, so it is one it knows
+            TraceResult::Error { rc } => Err(DdcError::from_kind_rc(rc)),
+            result => Ok(result),
+        }
+    }
+}
+
+impl VcpBackend for Replay {
+    fn get_vcp_value(&self, code: u8) -> Result<VcpValue> {
+        match self.next(TraceOp::GetVcp { code })? {
+            TraceResult::Value(v) => Ok(v),
+            _ => Err(DdcError::from_kind_rc(sys::DDCRC_BAD_DATA)),
+        }
+    }
+
+    fn set_vcp_value(&self, code: u8, value: u16) -> Result<()> {
+        self.next(TraceOp::SetVcp { code, value }).map(|_| ())
+    }
+
+    fn get_capabilities_string(&self) -> Result<String> {
+        match self.next(TraceOp::GetCapabilities)? {
+            TraceResult::Capabilities(c) => Ok(c),
+            _ => Err(DdcError::from_kind_rc(sys::DDCRC_BAD_DATA)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Adjustment, CancelToken, DisplayGroup, Easing};
+
+    #[test]
+    fn record_and_replay() {
+        let source = Replay::new(Trace {
+            display: None,
+            events: vec![
+                TraceEvent {
+                    op: TraceOp::GetVcp { code: 0x10 },
+                    result: TraceResult::Value(VcpValue::Continuous {
+                        max: 100,
+                        current: 30,
+                    }),
+                    start_micros: 0,
+                    duration_micros: 40_000,
+                },
+                TraceEvent {
+                    op: TraceOp::SetVcp {
+                        code: 0x10,
+                        value: 50,
+                    },
+                    result: TraceResult::Ok,
+                    start_micros: 50_000,
+                    duration_micros: 60_000,
+                },
+            ],
+        });
+
+        // recording the replay gives the same calls and results
+        let recorder = Recorder::new(&source);
+        recorder.get_vcp_value(0x10).unwrap();
+        recorder.set_vcp_value(0x10, 50).unwrap();
+        source.finish().unwrap();
+
+        let json = serde_json::to_string(&recorder.trace()).unwrap();
+        let replay = Replay::new(serde_json::from_str(&json).unwrap());
+        assert_eq!(
+            replay.get_vcp_value(0x10).unwrap(),
+            VcpValue::Continuous {
+                max: 100,
+                current: 30
+            }
+        );
+        assert!(replay.set_vcp_value(0x10, 60).is_err());
+        assert!(matches!(
+            replay.finish(),
+            Err(ReplayError::Mismatch(m)) if m[0].index == 1
+        ));
+    }
+
+    #[test]
+    fn realtime_replay_keeps_gaps() {
+        let event = |start_micros| TraceEvent {
+            op: TraceOp::GetCapabilities,
+            result: TraceResult::Capabilities(String::new()),
+            start_micros,
+            duration_micros: 10_000,
+        };
+        let replay = Replay::new(Trace {
+            display: None,
+            events: vec![event(1_000_000), event(1_040_000)],
+        })
+        .realtime(true);
+
+        let start = Instant::now();
+        replay.get_capabilities_string().unwrap();
+        replay.get_capabilities_string().unwrap();
+        // the second call starts 40ms after the first, and takes 10ms
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        replay.finish().unwrap();
+    }
+
+    #[test]
+    fn replay_through_group() {
+        let event = |op, result| TraceEvent {
+            op,
+            result,
+            start_micros: 0,
+            duration_micros: 0,
+        };
+        let brightness = TraceResult::Value(VcpValue::Continuous {
+            max: 200,
+            current: 60,
+        });
+        let trace = Trace {
+            display: None,
+            events: vec![
+                // the maximum is read once for the group, then again by the transition
+                event(TraceOp::GetVcp { code: 0x10 }, brightness.clone()),
+                event(TraceOp::GetVcp { code: 0x10 }, brightness),
+                event(
+                    TraceOp::SetVcp {
+                        code: 0x10,
+                        value: 100,
+                    },
+                    TraceResult::Ok,
+                ),
+            ],
+        };
+
+        let mut group = DisplayGroup::new();
+        group.push(Replay::new(trace), Adjustment::default());
+        let results = group.transition_percent(
+            0x10,
+            50.0,
+            Duration::ZERO,
+            Easing::Linear,
+            &CancelToken::new(),
+        );
+        assert_eq!(
+            results.into_iter().collect::<Result<Vec<_>>>().unwrap(),
+            [100]
+        );
+        group.members()[0].display.finish().unwrap();
+    }
+}
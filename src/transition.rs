@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::backend::VcpBackend;
+use crate::display::{Display, VcpValue};
+use crate::err::{DdcError, Result};
+use crate::sys;
+
+/// Shortest interval between two writes during a transition.
+const MIN_STEP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Easing curve for [`Display::transition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slowly, then accelerates.
+    EaseIn,
+    /// Starts quickly, then decelerates.
+    EaseOut,
+    /// Slow at both ends.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Map progress `t` (0.0 to 1.0) through the curve.
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Token for cancelling a [`Display::transition`] from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Value of the transition at progress `t` (0.0 to 1.0).
+fn interpolate(from: u16, to: u16, t: f64, easing: Easing) -> u16 {
+    let from = from as f64;
+    let to = to as f64;
+    (from + (to - from) * easing.apply(t)).round() as u16
+}
+
+/// Update the moving average of the write latency with a new sample.
+fn average_latency(avg: Option<Duration>, sample: Duration) -> Duration {
+    avg.map_or(sample, |avg| (avg * 3 + sample) / 4)
+}
+
+/// Gradually change a continuous feature from its current value to `target` over `duration`.
+///
+/// Steps are spaced by the average time a write takes on the display, so slow displays get
+/// fewer, larger steps instead of falling behind. The transition stops early if `cancel` is
+/// cancelled.
+///
+/// Returns the last value written, which is `target` (clamped to the feature's maximum) unless
+/// the transition was cancelled. [`Display::transition`] is the same for a display.
+pub fn transition<B: VcpBackend + ?Sized>(
+    backend: &B,
+    code: sys::DDCA_Vcp_Feature_Code,
+    target: u16,
+    duration: Duration,
+    easing: Easing,
+    cancel: &CancelToken,
+) -> Result<u16> {
+    let (from, target) = match backend.get_vcp_value(code)? {
+        VcpValue::Continuous { max, current } => (current, target.min(max)),
+        _ => return Err(DdcError::from_kind_rc(sys::DDCRC_INVALID_OPERATION)),
+    };
+    if from == target {
+        return Ok(target);
+    }
+
+    let start = Instant::now();
+    let mut last = from;
+    // averaged, so one slow write doesn't slow down the rest of the transition
+    let mut latency = None;
+    let mut interval = MIN_STEP_INTERVAL;
+
+    loop {
+        if cancel.is_cancelled() {
+            return Ok(last);
+        }
+
+        let step_start = Instant::now();
+        let t = if duration.is_zero() {
+            1.0
+        } else {
+            start.elapsed().as_secs_f64() / duration.as_secs_f64()
+        };
+
+        let value = interpolate(from, target, t, easing);
+        if value != last {
+            backend.set_vcp_value(code, value)?;
+            last = value;
+
+            // space out the steps by what the display can sustain
+            let avg = average_latency(latency, step_start.elapsed());
+            latency = Some(avg);
+            interval = avg.max(MIN_STEP_INTERVAL);
+        }
+
+        if t >= 1.0 {
+            return Ok(last);
+        }
+
+        thread::sleep(interval.saturating_sub(step_start.elapsed()));
+    }
+}
+
+impl Display {
+    /// Gradually change a continuous feature from its current value to `target` over `duration`,
+    /// see [`transition`].
+    pub fn transition(
+        &self,
+        code: sys::DDCA_Vcp_Feature_Code,
+        target: u16,
+        duration: Duration,
+        easing: Easing,
+        cancel: &CancelToken,
+    ) -> Result<u16> {
+        transition(self, code, target, duration, easing, cancel)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(interpolate(0, 100, 0.5, Easing::Linear), 50);
+        assert_eq!(interpolate(100, 0, 0.5, Easing::Linear), 50);
+        assert_eq!(interpolate(0, 100, 0.5, Easing::EaseIn), 25);
+        assert_eq!(interpolate(20, 80, 1.0, Easing::EaseOut), 80);
+    }
+
+    #[test]
+    fn test_average_latency() {
+        let ms = Duration::from_millis;
+        assert_eq!(average_latency(None, ms(400)), ms(400));
+        // a single slow write raises the interval, but it recovers afterwards
+        let avg = average_latency(Some(ms(100)), ms(500));
+        assert_eq!(avg, ms(200));
+        assert_eq!(average_latency(Some(avg), ms(100)), ms(175));
+    }
+}
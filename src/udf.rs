@@ -0,0 +1,581 @@
+//! User-defined feature (UDF) files.
+//!
+//! These are the feature definition files libddcutil reads when UDF processing is enabled with
+//! [`lib_set_udf`](crate::lib_set_udf). Each file describes the manufacturer-specific features of
+//! one monitor model:
+//!
+//! ```text
+//! # BenQ EW3280U
+//! MFG_ID        BNQ
+//! MODEL         BenQ EW3280U
+//! PRODUCT_CODE  32652
+//!
+//! FEATURE_CODE 0xE0 Low Blue Light
+//!    DESC   Reduces blue light emission
+//!    ATTRS  RW NC
+//!    VALUE  0x00 Off
+//!    VALUE  0x01 Multimedia
+//! ```
+//!
+//! Supported attributes are `RW`, `RO`, `WO` for the access mode and `C` (or `CONT`), `CCONT`,
+//! `NC`, `CNC`, `T` (or `TABLE`) for the feature type. Feature and value codes are hex even
+//! without the `0x` prefix. `#` starts a comment at the start of a line or after whitespace.
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{fs, io};
+
+use crate::MccsVersion;
+use crate::display_info::DisplayInfo;
+use crate::feature_metadata::{FeatureFlags, OwnedFeatureMetadata, OwnedFeatureValue};
+
+/// Error from reading or validating a UDF file.
+#[derive(Debug)]
+pub enum UdfError {
+    Io(io::Error),
+    /// The file is invalid. `line` is 1-based.
+    Parse {
+        path: Option<PathBuf>,
+        line: usize,
+        message: String,
+    },
+}
+
+impl UdfError {
+    fn parse(line: usize, message: impl Into<String>) -> Self {
+        UdfError::Parse {
+            path: None,
+            line,
+            message: message.into(),
+        }
+    }
+
+    fn with_path(self, p: &Path) -> Self {
+        match self {
+            UdfError::Parse { line, message, .. } => UdfError::Parse {
+                path: Some(p.to_owned()),
+                line,
+                message,
+            },
+            e => e,
+        }
+    }
+}
+
+impl std::fmt::Display for UdfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UdfError::Io(e) => write!(f, "{e}"),
+            UdfError::Parse {
+                path: Some(path),
+                line,
+                message,
+            } => write!(f, "{0}:{line}: {message}", path.display()),
+            UdfError::Parse {
+                path: None,
+                line,
+                message,
+            } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for UdfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UdfError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for UdfError {
+    fn from(e: io::Error) -> Self {
+        UdfError::Io(e)
+    }
+}
+
+/// One feature defined in a [`UdfFile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdfFeature {
+    pub code: u8,
+    pub name: String,
+    pub description: Option<String>,
+    /// One access flag (`RO`, `WO`, `RW`) and one type flag.
+    pub flags: FeatureFlags,
+    /// Names of the SL values of a non-continuous feature.
+    pub values: Vec<OwnedFeatureValue>,
+}
+
+impl UdfFeature {
+    pub fn value_name(&self, sl: u8) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|v| v.code == sl)
+            .map(|v| v.name.as_str())
+    }
+
+    /// The feature as library-style metadata, e.g. to show it alongside the standard features.
+    pub fn metadata(&self, vcp_version: MccsVersion) -> OwnedFeatureMetadata {
+        OwnedFeatureMetadata {
+            feature_code: self.code,
+            vcp_version,
+            name: self.name.clone(),
+            description: self.description.clone().unwrap_or_default(),
+            flags: self.flags | FeatureFlags::USER_DEFINED,
+            sl_values: self.values.clone(),
+        }
+    }
+}
+
+/// Feature definitions for one monitor model. See the [module docs](self) for the format.
+///
+/// Parse with [`str::parse`] or [`UdfFile::load`], and write with `Display` or
+/// [`UdfFile::save`]. Comments are not preserved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdfFile {
+    pub mfg_id: String,
+    pub model: String,
+    pub product_code: u16,
+    pub features: Vec<UdfFeature>,
+}
+
+impl UdfFile {
+    pub fn new(mfg_id: impl Into<String>, model: impl Into<String>, product_code: u16) -> Self {
+        UdfFile {
+            mfg_id: mfg_id.into(),
+            model: model.into(),
+            product_code,
+            features: Vec::new(),
+        }
+    }
+
+    /// Build an empty file for the model of a display.
+    pub fn for_display(info: &DisplayInfo) -> Self {
+        Self::new(info.manufacturer(), info.model(), info.product_code())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, UdfError> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e: UdfError| e.with_path(path))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// The name libddcutil looks for this file under, e.g. `BNQ-BenQ_EW3280U-32652.mccs`.
+    pub fn file_name(&self) -> String {
+        format!(
+            "{0}-{1}-{2}.mccs",
+            self.mfg_id,
+            self.model.replace(' ', "_"),
+            self.product_code
+        )
+    }
+
+    /// Whether the file describes the model of the given display.
+    pub fn applies_to(&self, info: &DisplayInfo) -> bool {
+        self.mfg_id == info.manufacturer()
+            && self.model == info.model()
+            && self.product_code == info.product_code()
+    }
+
+    pub fn feature(&self, code: u8) -> Option<&UdfFeature> {
+        self.features.iter().find(|f| f.code == code)
+    }
+
+    /// Directories searched for UDF files, most specific first: `$XDG_CONFIG_HOME/ddcutil`,
+    /// `$XDG_DATA_HOME/ddcutil`, then `ddcutil` in each of `$XDG_DATA_DIRS`.
+    pub fn search_dirs() -> Vec<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+        let home = var("HOME").map(PathBuf::from);
+
+        let config = var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|h| h.join(".config")));
+        let data = var("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|h| h.join(".local/share")));
+        let data_dirs =
+            var("XDG_DATA_DIRS").unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+        config
+            .into_iter()
+            .chain(data)
+            .chain(std::env::split_paths(&data_dirs))
+            .map(|d| d.join("ddcutil"))
+            .collect()
+    }
+
+    /// Load all UDF files (`*.mccs`) in a directory. A missing directory gives an empty list.
+    pub fn load_dir(dir: &Path) -> Result<Vec<(PathBuf, UdfFile)>, UdfError> {
+        let entries = match fs::read_dir(dir) {
+            Ok(e) => e,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "mccs") {
+                let file = Self::load(&path)?;
+                files.push((path, file));
+            }
+        }
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(files)
+    }
+
+    /// Find the UDF files in [`search_dirs`](Self::search_dirs) that apply to a display.
+    ///
+    /// Files are returned in search order, so earlier entries take precedence.
+    pub fn find_for(info: &DisplayInfo) -> Result<Vec<(PathBuf, UdfFile)>, UdfError> {
+        let mut found = Vec::new();
+        for dir in Self::search_dirs() {
+            found.extend(
+                Self::load_dir(&dir)?
+                    .into_iter()
+                    .filter(|(_, f)| f.applies_to(info)),
+            );
+        }
+
+        Ok(found)
+    }
+}
+
+fn parse_number<T: TryFrom<u32>>(s: &str) -> Option<T> {
+    let n = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => s.parse().ok()?,
+    };
+    T::try_from(n).ok()
+}
+
+/// Parse a feature or value code. Like ddcutil, codes are always hex, with or without `0x`, so
+/// `10` is 0x10.
+fn parse_code<T: TryFrom<u32>>(s: &str) -> Option<T> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    T::try_from(u32::from_str_radix(hex, 16).ok()?).ok()
+}
+
+/// Remove a comment from a line. `#` only starts a comment at the start of the line or after
+/// whitespace, so it can be used in names like "HDMI#1".
+fn strip_comment(line: &str) -> &str {
+    let mut prev = None;
+    for (i, c) in line.char_indices() {
+        if c == '#' && prev.is_none_or(char::is_whitespace) {
+            return &line[..i];
+        }
+        prev = Some(c);
+    }
+    line
+}
+
+/// The feature being defined, which `key` on `line` belongs to.
+fn current<'f>(
+    features: &'f mut [(usize, bool, UdfFeature)],
+    line: usize,
+    key: &str,
+) -> Result<&'f mut (usize, bool, UdfFeature), UdfError> {
+    features
+        .last_mut()
+        .ok_or_else(|| UdfError::parse(line, format!("{key} before the first FEATURE_CODE")))
+}
+
+fn parse_attrs(line: usize, attrs: &str) -> Result<FeatureFlags, UdfError> {
+    let mut access = None;
+    let mut kind = None;
+
+    for attr in attrs.split_whitespace() {
+        let (slot, flag) = match attr.to_ascii_uppercase().as_str() {
+            "RW" => (&mut access, FeatureFlags::RW),
+            "RO" => (&mut access, FeatureFlags::RO),
+            "WO" => (&mut access, FeatureFlags::WO),
+            "C" | "CONT" => (&mut kind, FeatureFlags::STD_CONT),
+            "CCONT" => (&mut kind, FeatureFlags::COMPLEX_CONT),
+            "NC" => (&mut kind, FeatureFlags::SIMPLE_NC),
+            "CNC" => (&mut kind, FeatureFlags::COMPLEX_NC),
+            "T" | "TABLE" => (&mut kind, FeatureFlags::NORMAL_TABLE),
+            _ => return Err(UdfError::parse(line, format!("unknown attribute {attr:?}"))),
+        };
+        if slot.replace(flag).is_some_and(|prev| prev != flag) {
+            return Err(UdfError::parse(
+                line,
+                format!("conflicting attribute {attr:?}"),
+            ));
+        }
+    }
+
+    match (access, kind) {
+        (Some(a), Some(k)) => Ok(a | k),
+        (None, _) => Err(UdfError::parse(line, "ATTRS is missing RW, RO or WO")),
+        (_, None) => Err(UdfError::parse(line, "ATTRS is missing the feature type")),
+    }
+}
+
+impl FromStr for UdfFile {
+    type Err = UdfError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mfg_id = None;
+        let mut model = None;
+        let mut product_code = None;
+        // features with the line they start on, and whether ATTRS has been seen
+        let mut features: Vec<(usize, bool, UdfFeature)> = Vec::new();
+
+        for (i, raw) in s.lines().enumerate() {
+            let line = i + 1;
+            let content = strip_comment(raw).trim();
+            if content.is_empty() {
+                continue;
+            }
+
+            let (key, rest) = content
+                .split_once(char::is_whitespace)
+                .map(|(k, r)| (k, r.trim()))
+                .unwrap_or((content, ""));
+            if rest.is_empty() {
+                return Err(UdfError::parse(line, format!("{key} needs a value")));
+            }
+
+            let header = |slot: &mut Option<String>| match slot.replace(rest.to_owned()) {
+                Some(_) => Err(UdfError::parse(line, format!("duplicate {key}"))),
+                None => Ok(()),
+            };
+
+            match key.to_ascii_uppercase().as_str() {
+                "MFG_ID" => header(&mut mfg_id)?,
+                "MODEL" => header(&mut model)?,
+                "PRODUCT_CODE" => {
+                    let code = parse_number(rest).ok_or_else(|| {
+                        UdfError::parse(line, format!("invalid product code {rest:?}"))
+                    })?;
+                    if product_code.replace(code).is_some() {
+                        return Err(UdfError::parse(line, "duplicate PRODUCT_CODE"));
+                    }
+                }
+                "FEATURE_CODE" => {
+                    let (code, name) = rest
+                        .split_once(char::is_whitespace)
+                        .map(|(c, n)| (c, n.trim()))
+                        .unwrap_or((rest, ""));
+                    let code: u8 = parse_code(code).ok_or_else(|| {
+                        UdfError::parse(line, format!("invalid feature code {code:?}"))
+                    })?;
+                    if name.is_empty() {
+                        return Err(UdfError::parse(line, "feature has no name"));
+                    }
+                    if let Some((first, ..)) = features.iter().find(|f| f.2.code == code) {
+                        return Err(UdfError::parse(
+                            line,
+                            format!("feature 0x{code:02X} is already defined on line {first}"),
+                        ));
+                    }
+
+                    features.push((
+                        line,
+                        false,
+                        UdfFeature {
+                            code,
+                            name: name.to_owned(),
+                            description: None,
+                            flags: FeatureFlags::empty(),
+                            values: Vec::new(),
+                        },
+                    ));
+                }
+                "DESC" => {
+                    let (.., feature) = current(&mut features, line, key)?;
+                    if feature.description.replace(rest.to_owned()).is_some() {
+                        return Err(UdfError::parse(line, "duplicate DESC"));
+                    }
+                }
+                "ATTRS" => {
+                    let (_, has_attrs, feature) = current(&mut features, line, key)?;
+                    if std::mem::replace(has_attrs, true) {
+                        return Err(UdfError::parse(line, "duplicate ATTRS"));
+                    }
+                    feature.flags = parse_attrs(line, rest)?;
+                }
+                "VALUE" => {
+                    let (.., feature) = current(&mut features, line, key)?;
+                    let (code, name) = rest
+                        .split_once(char::is_whitespace)
+                        .map(|(c, n)| (c, n.trim()))
+                        .ok_or_else(|| UdfError::parse(line, "VALUE needs a code and a name"))?;
+                    let code = parse_code(code).ok_or_else(|| {
+                        UdfError::parse(line, format!("invalid value code {code:?}"))
+                    })?;
+                    if feature.value_name(code).is_some() {
+                        return Err(UdfError::parse(
+                            line,
+                            format!("duplicate value 0x{code:02X}"),
+                        ));
+                    }
+                    feature.values.push(OwnedFeatureValue {
+                        code,
+                        name: name.to_owned(),
+                    });
+                }
+                _ => return Err(UdfError::parse(line, format!("unknown keyword {key:?}"))),
+            }
+        }
+
+        for (line, has_attrs, feature) in &features {
+            if !has_attrs {
+                return Err(UdfError::parse(
+                    *line,
+                    format!("feature 0x{0:02X} has no ATTRS", feature.code),
+                ));
+            }
+            if !feature.values.is_empty() && !feature.flags.is_non_continuous() {
+                return Err(UdfError::parse(
+                    *line,
+                    format!("feature 0x{0:02X} has values but is not NC", feature.code),
+                ));
+            }
+        }
+
+        let last_line = s.lines().count().max(1);
+        Ok(UdfFile {
+            mfg_id: mfg_id.ok_or_else(|| UdfError::parse(last_line, "missing MFG_ID"))?,
+            model: model.ok_or_else(|| UdfError::parse(last_line, "missing MODEL"))?,
+            product_code: product_code
+                .ok_or_else(|| UdfError::parse(last_line, "missing PRODUCT_CODE"))?,
+            features: features.into_iter().map(|f| f.2).collect(),
+        })
+    }
+}
+
+fn attrs_string(flags: FeatureFlags) -> String {
+    let access = if flags.contains(FeatureFlags::RW) {
+        "RW"
+    } else if flags.contains(FeatureFlags::WO) {
+        "WO"
+    } else {
+        "RO"
+    };
+    let kind = if flags.contains(FeatureFlags::COMPLEX_CONT) {
+        "CCONT"
+    } else if flags.is_continuous() {
+        "C"
+    } else if flags.intersects(FeatureFlags::SIMPLE_NC | FeatureFlags::WO_NC) {
+        "NC"
+    } else if flags.is_non_continuous() {
+        "CNC"
+    } else {
+        "T"
+    };
+
+    format!("{access} {kind}")
+}
+
+impl std::fmt::Display for UdfFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "MFG_ID        {0}", self.mfg_id)?;
+        writeln!(f, "MODEL         {0}", self.model)?;
+        writeln!(f, "PRODUCT_CODE  {0}", self.product_code)?;
+
+        for feature in &self.features {
+            writeln!(f)?;
+            writeln!(f, "FEATURE_CODE 0x{0:02X} {1}", feature.code, feature.name)?;
+            if let Some(desc) = &feature.description {
+                writeln!(f, "   DESC   {desc}")?;
+            }
+            writeln!(f, "   ATTRS  {0}", attrs_string(feature.flags))?;
+            for v in &feature.values {
+                writeln!(f, "   VALUE  0x{0:02X} {1}", v.code, v.name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BENQ: &str = "\
+# BenQ EW3280U
+MFG_ID        BNQ
+MODEL         BenQ EW3280U
+PRODUCT_CODE  0x7F8C
+
+FEATURE_CODE 0xE0 Low Blue Light   # vendor specific
+   DESC   Reduces blue light emission
+   ATTRS  RW NC
+   VALUE  0x00 Off
+   VALUE  0x01 Multimedia
+FEATURE_CODE 0xE1 Sharpness Boost
+   ATTRS  rw c
+";
+
+    #[test]
+    fn parse_and_write() {
+        let udf: UdfFile = BENQ.parse().unwrap();
+        assert_eq!(udf.product_code, 0x7F8C);
+        assert_eq!(udf.file_name(), "BNQ-BenQ_EW3280U-32652.mccs");
+
+        let lbl = udf.feature(0xE0).unwrap();
+        assert_eq!(lbl.flags, FeatureFlags::RW | FeatureFlags::SIMPLE_NC);
+        assert_eq!(lbl.value_name(1), Some("Multimedia"));
+        assert_eq!(
+            lbl.description.as_deref(),
+            Some("Reduces blue light emission")
+        );
+        assert_eq!(
+            udf.feature(0xE1).unwrap().flags,
+            FeatureFlags::RW | FeatureFlags::STD_CONT
+        );
+
+        assert_eq!(udf.to_string().parse::<UdfFile>().unwrap(), udf);
+    }
+
+    #[test]
+    fn bare_codes_are_hex() {
+        let udf: UdfFile = "\
+MFG_ID BNQ
+MODEL X
+PRODUCT_CODE 10
+FEATURE_CODE E0 Input#1 # comment
+   ATTRS RW NC
+   VALUE 10 Port#2
+#  VALUE 11 Port#3
+"
+        .parse()
+        .unwrap();
+        assert_eq!(udf.product_code, 10);
+
+        let feature = udf.feature(0xE0).unwrap();
+        assert_eq!(feature.name, "Input#1");
+        assert_eq!(feature.values.len(), 1);
+        assert_eq!(feature.value_name(0x10), Some("Port#2"));
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        let line = |s: &str| match s.parse::<UdfFile>().unwrap_err() {
+            UdfError::Parse { line, .. } => line,
+            e => panic!("unexpected error {e}"),
+        };
+
+        assert_eq!(
+            line("MFG_ID BNQ\nMODEL X\nPRODUCT_CODE 1\nVALUE 0 Off\n"),
+            4
+        );
+        assert_eq!(line("MFG_ID BNQ\n\nFEATURE_CODE 0x1FF Bad\n"), 3);
+        assert_eq!(line("FEATURE_CODE 0xE0 A\n  ATTRS RW NC T\n"), 2);
+        assert_eq!(
+            line("MFG_ID BNQ\nMODEL X\nPRODUCT_CODE 1\nFEATURE_CODE 0xE0 A\n"),
+            4
+        );
+        assert_eq!(line("MFG_ID BNQ\nMODEL X\n"), 2);
+    }
+}
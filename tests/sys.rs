@@ -76,3 +76,45 @@ fn test_testing2() {
 
     panic!(">>>>> see output")
 }
+
+#[test]
+#[ignore]
+fn test_display_ref() {
+    let list = get_display_info_list(false).unwrap();
+    let dref = list[0].dref();
+
+    dref.validate(false).unwrap();
+    assert!(!dref.to_string().is_empty());
+    assert_eq!(
+        format!("{dref:?}"),
+        format!("DisplayRef({0:?})", dref.to_string())
+    );
+}
+
+#[test]
+#[ignore]
+fn test_level_names() {
+    for level in [
+        OutputLevel::Terse,
+        OutputLevel::Normal,
+        OutputLevel::Verbose,
+        OutputLevel::VeryVerbose,
+    ] {
+        assert_eq!(level.to_string().parse::<OutputLevel>(), Ok(level));
+    }
+    assert_eq!("VV".parse::<OutputLevel>(), Ok(OutputLevel::VeryVerbose));
+    assert!("loud".parse::<OutputLevel>().is_err());
+
+    for level in [
+        SyslogLevel::Never,
+        SyslogLevel::Error,
+        SyslogLevel::Warning,
+        SyslogLevel::Notice,
+        SyslogLevel::Info,
+        SyslogLevel::Verbose,
+        SyslogLevel::Debug,
+    ] {
+        assert_eq!(level.to_string().parse::<SyslogLevel>(), Ok(level));
+    }
+    assert!("loud".parse::<SyslogLevel>().is_err());
+}